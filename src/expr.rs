@@ -0,0 +1,363 @@
+use crate::int::Int;
+use crate::parse::require_if_type_const;
+use crate::parse::{next_token, parse_literal, require_end, require_ident, require_punct};
+use crate::parse::{syntax, SyntaxError};
+use proc_macro::token_stream::IntoIter as TokenIter;
+use proc_macro::{Delimiter, Ident, Spacing, Span, TokenTree};
+
+#[derive(Copy, Clone)]
+pub(crate) enum Const {
+    Int(Int),
+    Bool(bool),
+}
+
+pub(crate) enum Expr {
+    Const(Const, Span),
+    Var(Ident),
+    // `R.addr`
+    Field(Ident, Ident),
+    // `#index`, `#len`, `#first`, `#last`
+    Meta(Ident),
+    Unary(UnaryOp, Span, Box<Expr>),
+    Binary(Box<Expr>, BinaryOp, Span, Box<Expr>),
+}
+
+#[derive(Copy, Clone)]
+pub(crate) enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub(crate) enum BinaryOp {
+    Mul,
+    Div,
+    Rem,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    BitAnd,
+    BitXor,
+    BitOr,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+// `|n| n * 2`
+pub(crate) struct Closure {
+    param: Ident,
+    body: Expr,
+}
+
+pub(crate) fn require_closure(iter: &mut TokenIter) -> Result<Closure, SyntaxError> {
+    require_punct(iter, '|')?;
+    let param = require_ident(iter)?;
+    require_punct(iter, '|')?;
+    let body = require_expr(iter)?;
+    Ok(Closure { param, body })
+}
+
+// Parses as much of the input as forms an expression, leaving behind any
+// subsequent tokens that cannot continue the expression.
+pub(crate) fn require_expr(iter: &mut TokenIter) -> Result<Expr, SyntaxError> {
+    require_binary(iter, 0)
+}
+
+fn require_binary(iter: &mut TokenIter, min_precedence: u8) -> Result<Expr, SyntaxError> {
+    let mut lhs = require_unary(iter)?;
+    while let Some((op, span, len)) = peek_binary_op(iter) {
+        if op.precedence() < min_precedence {
+            break;
+        }
+        for _ in 0..len {
+            let _ = iter.next();
+        }
+        let rhs = require_binary(iter, op.precedence() + 1)?;
+        lhs = Expr::Binary(Box::new(lhs), op, span, Box::new(rhs));
+    }
+    Ok(lhs)
+}
+
+fn require_unary(iter: &mut TokenIter) -> Result<Expr, SyntaxError> {
+    let token = next_token(iter)?;
+    match &token {
+        TokenTree::Punct(punct) if punct.as_char() == '-' || punct.as_char() == '!' => {
+            let op = if punct.as_char() == '-' {
+                UnaryOp::Neg
+            } else {
+                UnaryOp::Not
+            };
+            let operand = require_unary(iter)?;
+            Ok(Expr::Unary(op, punct.span(), Box::new(operand)))
+        }
+        TokenTree::Literal(lit) => match parse_literal(lit) {
+            Some(value) => Ok(Expr::Const(Const::Int(value.int), value.span)),
+            None => Err(syntax(token, "expected integer literal")),
+        },
+        TokenTree::Punct(punct) if punct.as_char() == '#' => match next_token(iter)? {
+            TokenTree::Ident(ident) => Ok(Expr::Meta(ident)),
+            other => Err(syntax(other, "expected ident")),
+        },
+        TokenTree::Ident(ident) => match ident.to_string().as_str() {
+            "true" => Ok(Expr::Const(Const::Bool(true), ident.span())),
+            "false" => Ok(Expr::Const(Const::Bool(false), ident.span())),
+            _ => {
+                if let Some(value) = require_if_type_const(iter, ident)? {
+                    return Ok(Expr::Const(Const::Int(value.int), value.span));
+                }
+                match require_if_field(iter) {
+                    Some(field) => Ok(Expr::Field(ident.clone(), field)),
+                    None => Ok(Expr::Var(ident.clone())),
+                }
+            }
+        },
+        TokenTree::Group(group)
+            if group.delimiter() == Delimiter::Parenthesis
+                || group.delimiter() == Delimiter::None =>
+        {
+            let mut iter = group.stream().into_iter();
+            let expr = require_expr(&mut iter)?;
+            require_end(&mut iter)?;
+            Ok(expr)
+        }
+        _ => Err(syntax(token, "expected expression")),
+    }
+}
+
+fn require_if_field(iter: &mut TokenIter) -> Option<Ident> {
+    let mut lookahead = iter.clone();
+    match (lookahead.next(), lookahead.next()) {
+        (Some(TokenTree::Punct(dot)), Some(TokenTree::Ident(field)))
+            if dot.as_char() == '.' && dot.spacing() == Spacing::Alone =>
+        {
+            *iter = lookahead;
+            Some(field)
+        }
+        _ => None,
+    }
+}
+
+pub(crate) fn peek_binary_op(iter: &TokenIter) -> Option<(BinaryOp, Span, usize)> {
+    let mut lookahead = iter.clone();
+    let Some(TokenTree::Punct(first)) = lookahead.next() else {
+        return None;
+    };
+    let second = match (first.spacing(), lookahead.next()) {
+        (Spacing::Joint, Some(TokenTree::Punct(second))) => Some(second.as_char()),
+        _ => None,
+    };
+    let (op, len) = match (first.as_char(), second) {
+        ('<', Some('<')) => (BinaryOp::Shl, 2),
+        ('>', Some('>')) => (BinaryOp::Shr, 2),
+        ('<', Some('=')) => (BinaryOp::Le, 2),
+        ('>', Some('=')) => (BinaryOp::Ge, 2),
+        ('=', Some('=')) => (BinaryOp::Eq, 2),
+        ('!', Some('=')) => (BinaryOp::Ne, 2),
+        ('&', Some('&')) => (BinaryOp::And, 2),
+        ('|', Some('|')) => (BinaryOp::Or, 2),
+        ('*', _) => (BinaryOp::Mul, 1),
+        ('/', _) => (BinaryOp::Div, 1),
+        ('%', _) => (BinaryOp::Rem, 1),
+        ('+', _) => (BinaryOp::Add, 1),
+        ('-', _) => (BinaryOp::Sub, 1),
+        ('&', _) => (BinaryOp::BitAnd, 1),
+        ('^', _) => (BinaryOp::BitXor, 1),
+        ('|', _) => (BinaryOp::BitOr, 1),
+        ('<', _) => (BinaryOp::Lt, 1),
+        ('>', _) => (BinaryOp::Gt, 1),
+        _ => return None,
+    };
+    Some((op, first.span(), len))
+}
+
+impl BinaryOp {
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Eq
+            | BinaryOp::Ne
+            | BinaryOp::Lt
+            | BinaryOp::Le
+            | BinaryOp::Gt
+            | BinaryOp::Ge => 3,
+            BinaryOp::BitOr => 4,
+            BinaryOp::BitXor => 5,
+            BinaryOp::BitAnd => 6,
+            BinaryOp::Shl | BinaryOp::Shr => 7,
+            BinaryOp::Add | BinaryOp::Sub => 8,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 9,
+        }
+    }
+
+    fn overflow_message(self) -> &'static str {
+        match self {
+            BinaryOp::Mul => "attempt to multiply with overflow",
+            BinaryOp::Div => "attempt to divide by zero",
+            BinaryOp::Rem => "attempt to calculate the remainder with a divisor of zero",
+            BinaryOp::Add => "attempt to add with overflow",
+            BinaryOp::Sub => "attempt to subtract with overflow",
+            BinaryOp::Shl => "attempt to shift left with overflow",
+            BinaryOp::Shr => "attempt to shift right with overflow",
+            BinaryOp::BitAnd | BinaryOp::BitXor | BinaryOp::BitOr => BITWISE_OVERFLOW,
+            _ => unreachable!(),
+        }
+    }
+}
+
+// Bitwise operators on negative values are computed in i128.
+const BITWISE_OVERFLOW: &str = "attempt to compute bitwise operation with overflow";
+
+impl Expr {
+    pub(crate) fn span(&self) -> Span {
+        match self {
+            Expr::Const(_, span) | Expr::Unary(_, span, _) | Expr::Binary(_, _, span, _) => *span,
+            Expr::Var(ident) | Expr::Field(ident, _) | Expr::Meta(ident) => ident.span(),
+        }
+    }
+
+    // Name of the first variable or field in the expression, as in `N` or
+    // `R.addr`.
+    pub(crate) fn first_var(&self) -> Option<String> {
+        match self {
+            Expr::Const(..) | Expr::Meta(_) => None,
+            Expr::Var(ident) => Some(ident.to_string()),
+            Expr::Field(ident, field) => Some(format!("{}.{}", ident, field)),
+            Expr::Unary(_, _, operand) => operand.first_var(),
+            Expr::Binary(lhs, _, _, rhs) => lhs.first_var().or_else(|| rhs.first_var()),
+        }
+    }
+
+    // Whether `known` accepts every variable and metavariable in the
+    // expression. A field `R.addr` is checked by its variable `R`.
+    pub(crate) fn names_known(&self, known: &dyn Fn(&str) -> bool) -> bool {
+        match self {
+            Expr::Const(..) => true,
+            Expr::Var(ident) | Expr::Field(ident, _) => known(&ident.to_string()),
+            Expr::Meta(ident) => known(&format!("#{}", ident)),
+            Expr::Unary(_, _, operand) => operand.names_known(known),
+            Expr::Binary(lhs, _, _, rhs) => lhs.names_known(known) && rhs.names_known(known),
+        }
+    }
+
+    // Variables are looked up by name, fields by `var.field`, and metavariables
+    // by name including the leading `#`.
+    pub(crate) fn eval(&self, env: &dyn Fn(&str) -> Option<Const>) -> Result<Const, SyntaxError> {
+        match self {
+            Expr::Const(value, _span) => Ok(*value),
+            Expr::Var(ident) => env(&ident.to_string()).ok_or_else(|| SyntaxError {
+                message: format!("cannot find value `{}` in this scope", ident),
+                span: ident.span(),
+            }),
+            Expr::Field(ident, field) => {
+                env(&format!("{}.{}", ident, field)).ok_or_else(|| SyntaxError {
+                    message: format!("cannot find field `{}` of `{}`", field, ident),
+                    span: field.span(),
+                })
+            }
+            Expr::Meta(ident) => env(&format!("#{}", ident)).ok_or_else(|| SyntaxError {
+                message: format!("unknown metavariable `#{}`", ident),
+                span: ident.span(),
+            }),
+            Expr::Unary(op, span, operand) => match (op, operand.eval(env)?) {
+                (UnaryOp::Neg, value) => Ok(Const::Int(value.to_int(operand.span())?.neg())),
+                (UnaryOp::Not, Const::Bool(bool)) => Ok(Const::Bool(!bool)),
+                (UnaryOp::Not, Const::Int(int)) => {
+                    int.checked_not()
+                        .map(Const::Int)
+                        .ok_or_else(|| SyntaxError {
+                            message: BITWISE_OVERFLOW.to_owned(),
+                            span: *span,
+                        })
+                }
+            },
+            Expr::Binary(lhs, op, span, rhs) => {
+                let lhs_value = lhs.eval(env)?;
+                if let BinaryOp::And | BinaryOp::Or = op {
+                    let short_circuit = *op == BinaryOp::Or;
+                    if lhs_value.to_bool(lhs.span())? == short_circuit {
+                        return Ok(Const::Bool(short_circuit));
+                    }
+                    return Ok(Const::Bool(rhs.eval(env)?.to_bool(rhs.span())?));
+                }
+                let rhs_value = rhs.eval(env)?;
+                if let (Const::Bool(a), Const::Bool(b), BinaryOp::Eq | BinaryOp::Ne) =
+                    (lhs_value, rhs_value, op)
+                {
+                    return Ok(Const::Bool((a == b) == (*op == BinaryOp::Eq)));
+                }
+                let a = lhs_value.to_int(lhs.span())?;
+                let b = rhs_value.to_int(rhs.span())?;
+                let result = match op {
+                    BinaryOp::Mul => a.checked_mul(b),
+                    BinaryOp::Div => a.checked_div(b),
+                    BinaryOp::Rem => a.checked_rem(b),
+                    BinaryOp::Add => a.checked_add(b),
+                    BinaryOp::Sub => a.checked_sub(b),
+                    BinaryOp::Shl => a.checked_shl(b),
+                    BinaryOp::Shr => a.checked_shr(b),
+                    BinaryOp::BitAnd => a.checked_bitand(b),
+                    BinaryOp::BitXor => a.checked_bitxor(b),
+                    BinaryOp::BitOr => a.checked_bitor(b),
+                    BinaryOp::Eq => return Ok(Const::Bool(a == b)),
+                    BinaryOp::Ne => return Ok(Const::Bool(a != b)),
+                    BinaryOp::Lt => return Ok(Const::Bool(a < b)),
+                    BinaryOp::Le => return Ok(Const::Bool(a <= b)),
+                    BinaryOp::Gt => return Ok(Const::Bool(a > b)),
+                    BinaryOp::Ge => return Ok(Const::Bool(a >= b)),
+                    BinaryOp::And | BinaryOp::Or => unreachable!(),
+                };
+                result.map(Const::Int).ok_or_else(|| SyntaxError {
+                    message: op.overflow_message().to_owned(),
+                    span: *span,
+                })
+            }
+        }
+    }
+}
+
+impl Const {
+    pub(crate) fn to_int(self, span: Span) -> Result<Int, SyntaxError> {
+        match self {
+            Const::Int(int) => Ok(int),
+            Const::Bool(_) => Err(SyntaxError {
+                message: "expected integer, found `bool`".to_owned(),
+                span,
+            }),
+        }
+    }
+
+    pub(crate) fn to_bool(self, span: Span) -> Result<bool, SyntaxError> {
+        match self {
+            Const::Bool(bool) => Ok(bool),
+            Const::Int(_) => Err(SyntaxError {
+                message: "expected `bool`, found integer".to_owned(),
+                span,
+            }),
+        }
+    }
+}
+
+impl Closure {
+    pub(crate) fn call(&self, arg: Int) -> Result<Const, SyntaxError> {
+        let param = self.param.to_string();
+        self.body.eval(&|name| {
+            if name == param {
+                Some(Const::Int(arg))
+            } else {
+                None
+            }
+        })
+    }
+
+    pub(crate) fn body(&self) -> &Expr {
+        &self.body
+    }
+}
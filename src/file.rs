@@ -0,0 +1,133 @@
+use crate::parse::{parse_string, SyntaxError};
+use proc_macro::{Literal, TokenStream};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// A CSV file whose first line names the columns, as in:
+//
+//     name,addr
+//     ctrl,0x00
+//     status,0x04
+pub(crate) struct Table {
+    // Absolute path, for tracking the file with `include_bytes!`.
+    pub(crate) path: String,
+    pub(crate) columns: Vec<String>,
+    pub(crate) rows: Vec<Vec<TokenStream>>,
+}
+
+pub(crate) fn read_table(lit: &Literal) -> Result<Table, SyntaxError> {
+    let span = lit.span();
+    let Some(relative) = parse_string(lit) else {
+        return Err(SyntaxError {
+            message: "expected string literal".to_owned(),
+            span,
+        });
+    };
+
+    // Relative to the crate being compiled, like `include_str!` is relative to
+    // the current file.
+    let mut path = env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    path.push(&relative);
+    let content = fs::read_to_string(&path).map_err(|error| SyntaxError {
+        message: format!("failed to read `{}`: {}", relative, error),
+        span,
+    })?;
+    let Some(path) = path.to_str() else {
+        return Err(SyntaxError {
+            message: format!("path `{}` is not valid UTF-8", relative),
+            span,
+        });
+    };
+
+    let error = |line: usize, message: String| SyntaxError {
+        message: format!("{}:{}: {}", relative, line, message),
+        span,
+    };
+
+    let mut lines = content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
+
+    let Some((header_line, header)) = lines.next() else {
+        return Err(error(
+            1,
+            "expected a header line naming the columns".to_owned(),
+        ));
+    };
+    let columns = split_row(header).ok_or_else(|| error(header_line, unterminated()))?;
+    for (i, column) in columns.iter().enumerate() {
+        let is_ident = column.starts_with(|ch: char| ch.is_alphabetic() || ch == '_')
+            && column.chars().all(|ch| ch.is_alphanumeric() || ch == '_');
+        if !is_ident {
+            let message = format!("column name `{}` is not an identifier", column);
+            return Err(error(header_line, message));
+        }
+        if columns[..i].contains(column) {
+            return Err(error(header_line, format!("duplicate column `{}`", column)));
+        }
+    }
+
+    let mut rows = Vec::new();
+    for (line, text) in lines {
+        let cells = split_row(text).ok_or_else(|| error(line, unterminated()))?;
+        if cells.len() != columns.len() {
+            let message = format!("expected {} columns, found {}", columns.len(), cells.len());
+            return Err(error(line, message));
+        }
+        let mut row = Vec::new();
+        for (column, cell) in columns.iter().zip(cells) {
+            let tokens = cell
+                .parse::<TokenStream>()
+                .map_err(|_| error(line, format!("cannot parse `{}` as Rust tokens", cell)))?;
+            // Also catches a cell holding only a comment, like `/* c */`.
+            if tokens.is_empty() {
+                return Err(error(line, format!("missing value for `{}`", column)));
+            }
+            row.push(tokens);
+        }
+        rows.push(row);
+    }
+
+    Ok(Table {
+        path: path.to_owned(),
+        columns,
+        rows,
+    })
+}
+
+// Splits on commas, except inside double quotes which may surround a value
+// like `"Foo<u8, u16>"`. A doubled `""` inside quotes is a literal quote.
+fn split_row(line: &str) -> Option<Vec<String>> {
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                cell.push('"');
+                let _ = chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                cells.push(cell.trim().to_owned());
+                cell.clear();
+            }
+            _ => cell.push(ch),
+        }
+    }
+    if quoted {
+        return None;
+    }
+    cells.push(cell.trim().to_owned());
+    Some(cells)
+}
+
+fn unterminated() -> String {
+    "unterminated quoted value".to_owned()
+}
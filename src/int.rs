@@ -0,0 +1,214 @@
+use std::cmp::Ordering;
+use std::fmt::{self, Display};
+
+// Sign and magnitude, so that every value of every primitive integer type is
+// representable. Zero is never negative.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Int {
+    negative: bool,
+    magnitude: u128,
+}
+
+impl Int {
+    pub(crate) fn new(negative: bool, magnitude: u128) -> Self {
+        Int {
+            negative: negative && magnitude != 0,
+            magnitude,
+        }
+    }
+
+    pub(crate) fn is_negative(self) -> bool {
+        self.negative
+    }
+
+    pub(crate) fn magnitude(self) -> u128 {
+        self.magnitude
+    }
+
+    pub(crate) fn to_u32(self) -> Option<u32> {
+        if self.negative {
+            None
+        } else {
+            u32::try_from(self.magnitude).ok()
+        }
+    }
+
+    fn to_i128(self) -> Option<i128> {
+        if self.negative {
+            0i128.checked_sub_unsigned(self.magnitude)
+        } else {
+            i128::try_from(self.magnitude).ok()
+        }
+    }
+
+    // Whether this is representable as a literal with the given suffix. Unsized
+    // types are assumed to be 64 bits.
+    pub(crate) fn fits(self, suffix: &str) -> bool {
+        let (signed, bits) = match int_type(suffix) {
+            Some((signed, 0)) => (signed, 64),
+            Some(ty) => ty,
+            None => return !self.negative || self.magnitude <= 1 << 127,
+        };
+        let max = int_max(signed, bits);
+        if self.negative {
+            signed && self.magnitude <= max + 1
+        } else {
+            self.magnitude <= max
+        }
+    }
+
+    pub(crate) fn successor(self) -> Option<Self> {
+        if self.negative {
+            Some(Int::new(true, self.magnitude - 1))
+        } else {
+            Some(Int::new(false, self.magnitude.checked_add(1)?))
+        }
+    }
+
+    pub(crate) fn neg(self) -> Self {
+        Int::new(!self.negative, self.magnitude)
+    }
+
+    pub(crate) fn checked_add(self, rhs: Self) -> Option<Self> {
+        if self.negative == rhs.negative {
+            let magnitude = self.magnitude.checked_add(rhs.magnitude)?;
+            Some(Int::new(self.negative, magnitude))
+        } else if self.magnitude >= rhs.magnitude {
+            Some(Int::new(self.negative, self.magnitude - rhs.magnitude))
+        } else {
+            Some(Int::new(rhs.negative, rhs.magnitude - self.magnitude))
+        }
+    }
+
+    pub(crate) fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.checked_add(rhs.neg())
+    }
+
+    pub(crate) fn checked_mul(self, rhs: Self) -> Option<Self> {
+        let magnitude = self.magnitude.checked_mul(rhs.magnitude)?;
+        Some(Int::new(self.negative != rhs.negative, magnitude))
+    }
+
+    // Rounds toward zero, like Rust's `/` operator.
+    pub(crate) fn checked_div(self, rhs: Self) -> Option<Self> {
+        let magnitude = self.magnitude.checked_div(rhs.magnitude)?;
+        Some(Int::new(self.negative != rhs.negative, magnitude))
+    }
+
+    // Takes the sign of the dividend, like Rust's `%` operator.
+    pub(crate) fn checked_rem(self, rhs: Self) -> Option<Self> {
+        let magnitude = self.magnitude.checked_rem(rhs.magnitude)?;
+        Some(Int::new(self.negative, magnitude))
+    }
+
+    pub(crate) fn checked_shl(self, rhs: Self) -> Option<Self> {
+        let shift = rhs.to_u32().filter(|shift| *shift < 128)?;
+        let magnitude = self.magnitude << shift;
+        if magnitude >> shift != self.magnitude {
+            return None;
+        }
+        Some(Int::new(self.negative, magnitude))
+    }
+
+    // Rounds toward negative infinity, like an arithmetic shift.
+    pub(crate) fn checked_shr(self, rhs: Self) -> Option<Self> {
+        let shift = rhs.to_u32().filter(|shift| *shift < 128)?;
+        let mut magnitude = self.magnitude >> shift;
+        if self.negative && magnitude << shift != self.magnitude {
+            magnitude += 1;
+        }
+        Some(Int::new(self.negative, magnitude))
+    }
+
+    // Bitwise operators treat negative values as two's complement.
+    pub(crate) fn checked_bitand(self, rhs: Self) -> Option<Self> {
+        self.bitwise(rhs, |a, b| a & b, |a, b| a & b)
+    }
+
+    pub(crate) fn checked_bitor(self, rhs: Self) -> Option<Self> {
+        self.bitwise(rhs, |a, b| a | b, |a, b| a | b)
+    }
+
+    pub(crate) fn checked_bitxor(self, rhs: Self) -> Option<Self> {
+        self.bitwise(rhs, |a, b| a ^ b, |a, b| a ^ b)
+    }
+
+    pub(crate) fn checked_not(self) -> Option<Self> {
+        // !x == -x - 1
+        if self.negative {
+            Some(Int::new(false, self.magnitude - 1))
+        } else {
+            Some(Int::new(true, self.magnitude.checked_add(1)?))
+        }
+    }
+
+    fn bitwise(
+        self,
+        rhs: Self,
+        unsigned: fn(u128, u128) -> u128,
+        signed: fn(i128, i128) -> i128,
+    ) -> Option<Self> {
+        if !self.negative && !rhs.negative {
+            Some(Int::new(false, unsigned(self.magnitude, rhs.magnitude)))
+        } else {
+            let int = signed(self.to_i128()?, rhs.to_i128()?);
+            Some(Int::new(int < 0, int.unsigned_abs()))
+        }
+    }
+}
+
+impl From<u32> for Int {
+    fn from(int: u32) -> Self {
+        Int::new(false, u128::from(int))
+    }
+}
+
+impl Display for Int {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if self.negative {
+            formatter.write_str("-")?;
+        }
+        Display::fmt(&self.magnitude, formatter)
+    }
+}
+
+impl Ord for Int {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, false) => self.magnitude.cmp(&other.magnitude),
+            (true, true) => other.magnitude.cmp(&self.magnitude),
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+        }
+    }
+}
+
+impl PartialOrd for Int {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Signedness and number of bits of a primitive integer type, with 0 bits for
+// usize and isize.
+pub(crate) fn int_type(name: &str) -> Option<(bool, u32)> {
+    match name {
+        "i8" => Some((true, 8)),
+        "i16" => Some((true, 16)),
+        "i32" => Some((true, 32)),
+        "i64" => Some((true, 64)),
+        "i128" => Some((true, 128)),
+        "isize" => Some((true, 0)),
+        "u8" => Some((false, 8)),
+        "u16" => Some((false, 16)),
+        "u32" => Some((false, 32)),
+        "u64" => Some((false, 64)),
+        "u128" => Some((false, 128)),
+        "usize" => Some((false, 0)),
+        _ => None,
+    }
+}
+
+pub(crate) fn int_max(signed: bool, bits: u32) -> u128 {
+    u128::MAX >> (128 - bits + u32::from(signed))
+}
@@ -36,10 +36,14 @@
 //! ```
 //!
 //! - If the input tokens contain a section surrounded by `#(` ... `)*` then
-//!   only that part is repeated.
+//!   only that part is repeated. A separator may be placed between `)` and `*`
+//!   as in `macro_rules!`: `#(T~N),*` expands to `T0, T1, T2` without a
+//!   trailing comma. Joint punctuation like `&&`, `||`, `=>`, or `::` also
+//!   forms a single separator.
 //!
 //! - The numeric counter can be pasted onto the end of some prefix to form
-//!   sequential identifiers.
+//!   sequential identifiers. Any chain of identifiers, literals, and the
+//!   counter joined by `~` is pasted into one identifier, as in `reg~N~_ctrl`.
 //!
 //! ```
 //! use seq_macro::seq;
@@ -60,9 +64,104 @@
 //! ```
 //!
 //! - Byte and character ranges are supported: `b'a'..=b'z'`, `'a'..='z'`.
+//!   Escapes may be used in the bounds, as in `b'\0'..=b'\x7f'` or
+//!   `'\u{391}'..='\u{3A9}'`.
+//!
+//! - Negative bounds are supported: `-4..4`, `-128i8..=127i8`. A negative
+//!   counter pasted into an identifier is spelled `Neg`, as in `OffNeg3`.
+//!
+//! - The `MIN`, `MAX`, and `BITS` constants of primitive integer types, and
+//!   `char::MAX`, may be used as bounds: `u8::MIN..=u8::MAX`, `0..u32::BITS`.
+//!   `MIN` and `MAX` give the generated literals that type's suffix.
+//!
+//! - Every generated value must fit in the type named by the suffix, so
+//!   `0u8..256` is accepted but `250u8..=300` is an error.
+//!
+//! - A reversed range like `10..3` is an error, unless its bounds are computed
+//!   from earlier variables. A range that produces no values at all, like
+//!   `5..5`, is a warning unless the invocation says
+//!   `seq!(N in 5..5 where allow_empty { ... })`. The
+//!   warning is an item, placed as described below for `env!`.
+//!
+//! - The end bound may be omitted when the start has a type suffix or is a byte
+//!   or character literal, as in `0u8..` or `b'a'..`. The range then extends
+//!   through the maximum value of that type.
+//!
+//! - A bound may be a constant expression, such as `0..(1 << 4)`,
+//!   `0..2 * 8 + 1`, or an `$n:expr` forwarded by macro_rules. Its formatting
+//!   comes from the leftmost literal in the expression.
+//!
+//! - A bound may be read from an environment variable during expansion, as in
+//!   `0..env!("NPROC")`, for example one set by a build script through
+//!   `cargo:rustc-env`. Changing the variable triggers a rebuild through a
+//!   `const _` item referring to it. The item goes before a body containing a
+//!   `let` statement or an item that only a module or block allows, such as
+//!   `struct` or `impl`, or else into the first function body in the expansion.
+//!   Without either, as for an expression or a lone macro call, the item is
+//!   left out and the variable is not tracked.
+//!
+//! - A parenthesized range can be followed by the iterator adapters `rev()`,
+//!   `step_by(k)`, `skip(n)`, `take(n)`, and `chain(range)`, as in
+//!   `(0..64).step_by(8).rev()` or `(0..4).chain(8..12)`, as well as `map` and
+//!   `filter` with a closure of integer arithmetic, comparisons, bit operations
+//!   and boolean logic: `(0..32).filter(|n| n % 2 == 0).map(|n| n * 4)`.
+//!
+//! - Inside the body, `#{N * 8 + 4}` evaluates an expression of the counter
+//!   into a literal, and `Prefix~{N - 1}` pastes a computed value. In the body
+//!   of a nested `seq!`, an expression naming the inner variables is left for
+//!   the inner invocation to evaluate.
+//!
+//! - An interpolation may convert its value with `:char`, `:byte`, `:str`,
+//!   `:int`, `:bool`, or `:float`, as in `[#(#{N:char}),*]`. A value that the
+//!   requested kind cannot represent is an error.
+//!
+//! - A format spec overrides the radix and padding taken from the bounds. It
+//!   ends an interpolation, as in `#{N:X}`, `#{N:o}`, `#{N:08b}`, or `#{N:04}`
+//!   for zero-padded decimal, or a pasted computed value, as in `Reg~{N:X}`. A
+//!   spec without a width, such as `#{N:x}` or `#{N:d}`, emits no padding. One
+//!   with both a width and a radix may also follow a variable pasted after `~`,
+//!   as in `Pin~N:02x`.
+//!
+//! - Formatting for the whole expansion can be set after the ranges, as in
+//!   `seq!(N in 0..100 where pad = auto, suffix = none, radix = hex { ... })`.
+//!   `pad` is `auto` to zero-pad to the widest value, `none`, or a width;
+//!   `suffix` is `none` or an integer type; `radix` is `bin`, `oct`, `dec`,
+//!   `hex`, or `HEX`.
+//!
+//! - `#index` is the 0-based position of the current repetition, `#len` the
+//!   number of repetitions, and `#first` and `#last` are `true` or `false` at
+//!   the edges. `#len` may also be used outside of a `#(...)*` repetition.
+//!   Inside the body of a nested `seq!`, these refer to the inner invocation.
+//!
+//! - `#if N % 2 == 0 { ... } #else { ... }` selects code per repetition. The
+//!   condition may use the counter and the metavariables above, and `#else #if`
+//!   chains are supported. In the body of a nested `seq!`, a chain whose
+//!   conditions name the inner variables is left for the inner invocation.
+//!
+//! - Several variables may be declared, as in
+//!   `seq!(R in 0..4, C in 0..R { ... })`. The body repeats for every
+//!   combination in row-major order, and each range may use the variables
+//!   declared before it.
+//!
+//! - `T in [u8, i16, Foo<Bar>, "name", 0x10]` repeats the body for each
+//!   element of a list. Elements may be any tokens, including commas inside
+//!   generic arguments as in `HashMap<u8, u16>`, and can be pasted with `~`
+//!   when they are a single identifier or integer.
+//!
+//! - A list of records, as in `R in [{ name: ctrl, addr: 0x00 }, ...]`, gives
+//!   access to each field with `R.name` or `get_~R.name`.
+//!
+//! - `R in file("regs.csv")` reads records from a CSV file, relative to the
+//!   crate's Cargo.toml, whose first line names the fields. Editing the file
+//!   triggers a rebuild through an item placed as described above for `env!`.
+//!
+//! - `(N, T) in zip(0..3, [u8, u16, u32])` walks several sequences in lockstep.
 //!
 //! - If the range bounds are written in binary, octal, hex, or with zero
 //!   padding, those features are preserved in any generated tokens.
+//!   Underscores grouping the digits, as in `0x0000_0000`, are kept in
+//!   generated literals but left out of pasted identifiers unless the
+//!   invocation says `where paste_groups`.
 //!
 //! ```
 //! use seq_macro::seq;
@@ -86,10 +185,16 @@
     clippy::wildcard_imports
 )]
 
+mod expr;
+mod file;
+mod int;
 mod parse;
 
+use crate::expr::{require_expr, Closure, Const, Expr};
+use crate::int::Int;
 use crate::parse::*;
-use proc_macro::{Delimiter, Group, Ident, Literal, Span, TokenStream, TokenTree};
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+use std::ascii;
 use std::iter;
 
 #[proc_macro]
@@ -98,30 +203,126 @@ pub fn seq(input: TokenStream) -> TokenStream {
 }
 
 struct Range {
-    begin: u64,
-    end: u64,
+    begin: Int,
+    end: Int,
     inclusive: bool,
+    adapters: Vec<Adapter>,
     kind: Kind,
     suffix: String,
     width: usize,
     radix: Radix,
+    grouping: Grouping,
+    // Span of the end bound of a range like `10..3`, which yields no values.
+    reversed: Option<Span>,
+    span: Span,
+}
+
+// `where allow_empty, pad = auto, suffix = none, radix = hex`, applying to the
+// whole invocation.
+#[derive(Default)]
+struct Options {
+    allow_empty: bool,
+    paste_groups: bool,
+    pad: Option<Pad>,
+    // Empty for `suffix = none`.
+    suffix: Option<(String, Span)>,
+    radix: Option<Radix>,
+}
+
+#[derive(Copy, Clone)]
+enum Pad {
+    // Zero padding to the digit count of the variable's widest value.
+    Auto,
+    Width(usize),
+}
+
+// The right side of `in`.
+enum Source {
+    Range(Range),
+    List(Vec<TokenStream>),
+    Zip(Vec<Source>),
+    // `file("regs.csv")`
+    File(Literal),
+}
+
+// Iterator adapters applied in order after the range, as in
+// `(0..64).step_by(8).rev()`.
+enum Adapter {
+    Rev,
+    StepBy(usize),
+    Skip(usize),
+    Take(usize),
+    Chain(Box<Range>),
+    Map(Closure),
+    Filter(Closure),
 }
 
 struct Value {
-    int: u64,
+    int: Int,
     kind: Kind,
     suffix: String,
     width: usize,
     radix: Radix,
+    grouping: Grouping,
     span: Span,
 }
 
-struct Splice<'a> {
-    int: u64,
+// The value of one variable in one repetition.
+#[derive(Clone)]
+enum Binding {
+    Int(Splice),
+    Tokens(TokenStream),
+    Record(Record),
+}
+
+// `{ name: ctrl, addr: 0x00 }`
+#[derive(Clone)]
+struct Record {
+    fields: Vec<(String, Binding)>,
+    span: Span,
+}
+
+#[derive(Clone)]
+struct Splice {
+    int: Int,
     kind: Kind,
-    suffix: &'a str,
+    suffix: String,
     width: usize,
     radix: Radix,
+    grouping: Grouping,
+}
+
+// Underscores between digits of the bounds, as in `0x0000_0000`, as the sizes
+// of the groups counted from the right. The last size repeats.
+#[derive(Clone, Default)]
+struct Grouping {
+    sizes: Vec<usize>,
+    // `where paste_groups` keeps them in pasted identifiers too.
+    paste: bool,
+}
+
+// `N:02x`, `N:X`, `N:d`. Without a width, no padding is applied.
+#[derive(Copy, Clone)]
+struct Format {
+    radix: Radix,
+    width: usize,
+}
+
+// What may follow the expression in `#{N:02x}` or `#{N:char}`.
+enum Modifier {
+    Format(Format),
+    Conversion(Conversion, Span),
+}
+
+// `N:byte`, `N:char`, `N:str`, `N:int`, `N:bool`, `N:float`
+#[derive(Copy, Clone, PartialEq)]
+enum Conversion {
+    Byte,
+    Char,
+    Str,
+    Int,
+    Bool,
+    Float,
 }
 
 #[derive(Copy, Clone, PartialEq)]
@@ -140,136 +341,875 @@ enum Radix {
     UpperHex,
 }
 
+impl Range {
+    fn check_reversed(&self) -> Result<(), SyntaxError> {
+        if let Some(span) = self.reversed {
+            return Err(SyntaxError {
+                message: "range end is less than its start".to_owned(),
+                span,
+            });
+        }
+        for adapter in &self.adapters {
+            if let Adapter::Chain(range) = adapter {
+                range.check_reversed()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn ints(&self) -> Box<dyn Iterator<Item = Result<Int, SyntaxError>> + '_> {
+        // A `rev()` directly on the range counts down from the end instead of
+        // collecting, which matters for open-ended ranges like `(0u32..).rev()`.
+        let leading_revs = self
+            .adapters
+            .iter()
+            .take_while(|adapter| matches!(adapter, Adapter::Rev))
+            .count();
+        let reversed = leading_revs % 2 == 1;
+        let mut ints: Box<dyn Iterator<Item = Result<Int, SyntaxError>>> = match self.kind {
+            Kind::Int | Kind::Byte if reversed => {
+                let begin = self.begin;
+                let one = Int::from(1);
+                let last = if self.inclusive {
+                    Some(self.end)
+                } else {
+                    self.end.checked_sub(one)
+                };
+                let predecessor = move |int: &Int| int.checked_sub(one);
+                let ints = iter::successors(last, predecessor).take_while(move |int| *int >= begin);
+                Box::new(ints.map(Ok))
+            }
+            Kind::Int | Kind::Byte => {
+                let end = self.end;
+                let inclusive = self.inclusive;
+                let ints = iter::successors(Some(self.begin), |int| int.successor())
+                    .take_while(move |int| if inclusive { *int <= end } else { *int < end });
+                Box::new(ints.map(Ok))
+            }
+            Kind::Char => {
+                let begin = char::from_u32(self.begin.to_u32().unwrap()).unwrap();
+                let end = char::from_u32(self.end.to_u32().unwrap()).unwrap();
+                let int = |ch| Ok(Int::from(u32::from(ch)));
+                match (self.inclusive, reversed) {
+                    (true, false) => Box::new((begin..=end).map(int)),
+                    (true, true) => Box::new((begin..=end).rev().map(int)),
+                    (false, false) => Box::new((begin..end).map(int)),
+                    (false, true) => Box::new((begin..end).rev().map(int)),
+                }
+            }
+        };
+        for adapter in &self.adapters[leading_revs..] {
+            ints = match adapter {
+                Adapter::Rev => Box::new(self.collect_reversed(ints)),
+                Adapter::StepBy(step) => Box::new(ints.step_by(*step)),
+                Adapter::Skip(n) => Box::new(ints.skip(*n)),
+                Adapter::Take(n) => Box::new(ints.take(*n)),
+                Adapter::Chain(range) => Box::new(ints.chain(range.ints())),
+                Adapter::Map(closure) => Box::new(ints.map(move |int| {
+                    let body = closure.body();
+                    let int = closure.call(int?)?.to_int(body.span())?;
+                    self.kind.check(int, &self.suffix, body.span())?;
+                    Ok(int)
+                })),
+                Adapter::Filter(closure) => Box::new(ints.filter_map(move |int| {
+                    let keep = int.and_then(|int| {
+                        let keep = closure.call(int)?.to_bool(closure.body().span())?;
+                        Ok(if keep { Some(int) } else { None })
+                    });
+                    keep.transpose()
+                })),
+            };
+        }
+        ints
+    }
+
+    // A `rev()` after other adapters has to see every value first.
+    fn collect_reversed(
+        &self,
+        ints: Box<dyn Iterator<Item = Result<Int, SyntaxError>> + '_>,
+    ) -> impl Iterator<Item = Result<Int, SyntaxError>> {
+        const MAX_REVERSED: usize = 1 << 16;
+        let mut values = Vec::from_iter(ints.take(MAX_REVERSED + 1));
+        if values.len() > MAX_REVERSED {
+            values = vec![Err(SyntaxError {
+                message: format!("cannot reverse more than {} values", MAX_REVERSED),
+                span: self.span,
+            })];
+        }
+        values.into_iter().rev()
+    }
+}
+
 impl<'a> IntoIterator for &'a Range {
-    type Item = Splice<'a>;
-    type IntoIter = Box<dyn Iterator<Item = Splice<'a>> + 'a>;
+    type Item = Result<Splice, SyntaxError>;
+    type IntoIter = Box<dyn Iterator<Item = Self::Item> + 'a>;
 
     fn into_iter(self) -> Self::IntoIter {
         let splice = move |int| Splice {
             int,
             kind: self.kind,
-            suffix: &self.suffix,
+            suffix: self.suffix.clone(),
             width: self.width,
             radix: self.radix,
+            grouping: self.grouping.clone(),
         };
-        match self.kind {
-            Kind::Int | Kind::Byte => {
-                if self.inclusive {
-                    Box::new((self.begin..=self.end).map(splice))
-                } else {
-                    Box::new((self.begin..self.end).map(splice))
+        Box::new(self.ints().map(move |int| int.map(splice)))
+    }
+}
+
+impl Source {
+    // A reversed range is an error unless its bounds were computed from
+    // earlier variables, as in `C in (R + 2)..4`.
+    fn check_reversed(&self) -> Result<(), SyntaxError> {
+        match self {
+            Source::Range(range) => range.check_reversed(),
+            Source::Zip(sources) => sources.iter().try_for_each(Source::check_reversed),
+            Source::List(_) | Source::File(_) => Ok(()),
+        }
+    }
+
+    // Number of variables bound by each element, as in `(N, T) in zip(..)`.
+    fn arity(&self) -> usize {
+        match self {
+            Source::Range(_) | Source::List(_) | Source::File(_) => 1,
+            Source::Zip(sources) => sources.iter().map(Source::arity).sum(),
+        }
+    }
+
+    // Files that were read are added to `files`, to be tracked for rebuilds.
+    fn elements(&self, files: &mut Vec<String>) -> Result<Vec<Vec<Binding>>, SyntaxError> {
+        match self {
+            Source::Range(range) => range
+                .into_iter()
+                .map(|splice| Ok(vec![Binding::Int(splice?)]))
+                .collect(),
+            Source::List(list) => list
+                .iter()
+                .map(|tokens| Ok(vec![Binding::from_element(tokens)?]))
+                .collect(),
+            Source::Zip(sources) => {
+                let mut zipped = Vec::<Vec<Binding>>::new();
+                for (i, source) in sources.iter().enumerate() {
+                    let elements = source.elements(files)?;
+                    if i == 0 {
+                        zipped = elements;
+                    } else if elements.len() != zipped.len() {
+                        return Err(SyntaxError {
+                            message: format!(
+                                "zipped sequences have different lengths: {} and {}",
+                                zipped.len(),
+                                elements.len(),
+                            ),
+                            span: Span::call_site(),
+                        });
+                    } else {
+                        for (row, element) in zipped.iter_mut().zip(elements) {
+                            row.extend(element);
+                        }
+                    }
                 }
+                Ok(zipped)
             }
-            Kind::Char => {
-                let begin = char::from_u32(self.begin as u32).unwrap();
-                let end = char::from_u32(self.end as u32).unwrap();
-                let int = |ch| u64::from(u32::from(ch));
-                if self.inclusive {
-                    Box::new((begin..=end).map(int).map(splice))
-                } else {
-                    Box::new((begin..end).map(int).map(splice))
+            Source::File(path) => {
+                let table = file::read_table(path)?;
+                let mut elements = Vec::new();
+                for row in &table.rows {
+                    let mut fields = Vec::new();
+                    for (column, cell) in table.columns.iter().zip(row) {
+                        fields.push((column.clone(), Binding::from_element(cell)?));
+                    }
+                    elements.push(vec![Binding::Record(Record {
+                        fields,
+                        span: path.span(),
+                    })]);
+                }
+                if !files.contains(&table.path) {
+                    files.push(table.path);
+                }
+                Ok(elements)
+            }
+        }
+    }
+}
+
+impl Binding {
+    // Elements that are an integer, byte, or character literal behave like the
+    // values of a range, and elements in curly braces are records. Anything
+    // else is substituted verbatim.
+    fn from_element(tokens: &TokenStream) -> Result<Self, SyntaxError> {
+        let mut iter = tokens.clone().into_iter();
+        if let (Some(TokenTree::Group(group)), None) = (iter.next(), iter.next()) {
+            if group.delimiter() == Delimiter::Brace {
+                let mut fields = Vec::new();
+                for (name, value) in require_fields(&group)? {
+                    fields.push((name.to_string(), Binding::from_element(&value)?));
                 }
+                return Ok(Binding::Record(Record {
+                    fields,
+                    span: group.span(),
+                }));
             }
         }
+
+        let mut iter = tokens.clone().into_iter();
+        match require_value(&mut iter) {
+            Ok(value) if iter.next().is_none() => Ok(Binding::Int(Splice {
+                int: value.int,
+                kind: value.kind,
+                suffix: value.suffix,
+                width: value.width,
+                radix: value.radix,
+                grouping: value.grouping,
+            })),
+            _ => Ok(Binding::Tokens(tokens.clone())),
+        }
+    }
+
+    fn tokens(&self, span: Span) -> Vec<TokenTree> {
+        match self {
+            Binding::Int(splice) => splice.tokens(span),
+            Binding::Tokens(tokens) => Vec::from_iter(tokens.clone()),
+            Binding::Record(_) => unreachable!(),
+        }
+    }
+}
+
+impl Record {
+    fn field(&self, name: &str) -> Option<&Binding> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_field, binding)| binding)
+    }
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Int => "integer",
+            Kind::Byte => "byte",
+            Kind::Char => "character",
+        }
+    }
+
+    fn contains(self, int: Int) -> bool {
+        match self {
+            Kind::Int => true,
+            Kind::Byte => !int.is_negative() && int.magnitude() <= 0xFF,
+            Kind::Char => int.to_u32().and_then(char::from_u32).is_some(),
+        }
+    }
+
+    // Whether the value can be emitted as a literal of this kind and suffix.
+    fn check(self, int: Int, suffix: &str, span: Span) -> Result<(), SyntaxError> {
+        let message = if !self.contains(int) {
+            format!("value out of range for {} range", self.name())
+        } else if !int.fits(suffix) {
+            format!("value out of range for `{}`", suffix)
+        } else {
+            return Ok(());
+        };
+        Err(SyntaxError { message, span })
     }
 }
 
 fn seq_impl(input: TokenStream) -> Result<TokenStream, SyntaxError> {
     let mut iter = input.into_iter();
-    let var = require_ident(&mut iter)?;
-    require_keyword(&mut iter, "in")?;
-    let begin = require_value(&mut iter)?;
-    require_punct(&mut iter, '.')?;
-    require_punct(&mut iter, '.')?;
-    let inclusive = require_if_punct(&mut iter, '=')?;
-    let end = require_value(&mut iter)?;
+    let mut patterns = Vec::new();
+    let mut clauses = Vec::new();
+    loop {
+        patterns.push(require_pattern(&mut iter)?);
+        require_keyword(&mut iter, "in")?;
+        clauses.push(require_clause(&mut iter)?);
+        if !require_if_punct(&mut iter, ',')? {
+            break;
+        }
+    }
+    let options = require_options(&mut iter)?;
     let body = require_braces(&mut iter)?;
     require_end(&mut iter)?;
 
-    let range = validate_range(begin, end, inclusive)?;
+    let vars = patterns.concat();
+    let mut files = Vec::new();
+    let (mut rows, empty) = product(&patterns, &clauses, &mut files)?;
+    apply_options(&mut rows, &options)?;
+    let mut env_vars = Vec::new();
+    for clause in &clauses {
+        find_env_vars(clause.clone(), &mut env_vars);
+    }
 
     let mut found_repetition = false;
-    let expanded = expand_repetitions(&var, &range, body.clone(), &mut found_repetition);
-    if found_repetition {
-        Ok(expanded)
+    let repetitions = expand_repetitions(&vars, &rows, body.clone(), &mut found_repetition)?;
+    let expanded = if found_repetition {
+        repetitions
     } else {
         // If no `#(...)*`, repeat the entire body.
-        Ok(repeat(&var, &range, &body))
+        repeat(&vars, &rows, &body)?
+    };
+
+    let mut items = track_files(&files);
+    items.extend(track_env_vars(&env_vars));
+    match empty {
+        Some(span) if !options.allow_empty => items.extend(warn_empty(span)),
+        _ => {}
     }
+    Ok(with_items(items, body, expanded))
 }
 
-fn repeat(var: &Ident, range: &Range, body: &TokenStream) -> TokenStream {
-    let mut repeated = TokenStream::new();
-    for value in range {
-        repeated.extend(substitute_value(var, &value, body.clone()));
+// The items are only emitted where an item is known to be legal without
+// changing how the rest of the expansion compiles: before a body that has a
+// statement only allowed in a module or block, or else at the start of the
+// first function body in the expansion. Elsewhere, such as in expression
+// position or in an impl block, they are left out.
+fn with_items(items: TokenStream, body: TokenStream, expanded: TokenStream) -> TokenStream {
+    if items.is_empty() {
+        expanded
+    } else if in_module_or_block(body) {
+        let mut tokens = items;
+        tokens.extend(expanded);
+        tokens
+    } else {
+        let mut items = Some(items);
+        insert_in_fn_body(expanded, &mut items)
     }
-    repeated
 }
 
-fn substitute_value(var: &Ident, splice: &Splice, body: TokenStream) -> TokenStream {
-    let mut tokens = Vec::from_iter(body);
+// There is no stable way for a procedural macro to emit a warning, but using a
+// deprecated item produces one, pointing at the span of the use.
+//
+//     const _: () = {
+//         #[deprecated(note = "...")]
+//         struct EmptyRange;
+//         let _ = EmptyRange;
+//     };
+fn warn_empty(span: Span) -> TokenStream {
+    let note = "range in seq! is empty; add `where allow_empty` if this is intended";
+    let item = format!(
+        "#[deprecated(note = {})] struct EmptyRange; let _ = ",
+        Literal::string(note),
+    );
+    let mut warning = item.parse::<TokenStream>().unwrap();
+    warning.extend([
+        TokenTree::Ident(Ident::new("EmptyRange", span)),
+        TokenTree::Punct(Punct::new(';', Spacing::Alone)),
+    ]);
+    let item = TokenStream::from_iter([
+        TokenTree::Group(Group::new(Delimiter::Brace, warning)),
+        TokenTree::Punct(Punct::new(';', Spacing::Alone)),
+    ]);
+    let mut tokens = "const _: () =".parse::<TokenStream>().unwrap();
+    tokens.extend(item);
+    tokens
+}
 
+// Whether some statement in the body, or in a `#(...)*` repetition of it, can
+// only appear in a module or block, like `let x = 0;` or `struct S;`.
+fn in_module_or_block(body: TokenStream) -> bool {
+    let tokens = Vec::from_iter(body);
+    let mut statement_start = true;
     let mut i = 0;
     while i < tokens.len() {
-        // Substitute our variable by itself, e.g. `N`.
-        let replace = match &tokens[i] {
-            TokenTree::Ident(ident) => ident.to_string() == var.to_string(),
+        if statement_start && is_module_or_block_statement(&tokens[i..]) {
+            return true;
+        }
+        if let (TokenTree::Punct(pound), Some(TokenTree::Group(group))) =
+            (&tokens[i], tokens.get(i + 1))
+        {
+            if pound.as_char() == '#' {
+                match group.delimiter() {
+                    Delimiter::Parenthesis if in_module_or_block(group.stream()) => return true,
+                    // An attribute leaves the statement start where it is.
+                    Delimiter::Bracket => {
+                        i += 2;
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        statement_start = match &tokens[i] {
+            TokenTree::Punct(punct) => punct.as_char() == ';',
+            TokenTree::Group(group) => group.delimiter() == Delimiter::Brace,
             _ => false,
         };
-        if replace {
-            let original_span = tokens[i].span();
-            let mut literal = splice.literal();
-            literal.set_span(original_span);
-            tokens[i] = TokenTree::Literal(literal);
-            i += 1;
-            continue;
+        i += 1;
+    }
+    false
+}
+
+fn is_module_or_block_statement(tokens: &[TokenTree]) -> bool {
+    let mut iter = tokens.iter().peekable();
+    // Visibility and `unsafe`, as in `pub(crate) unsafe trait`.
+    while let Some(TokenTree::Ident(ident)) = iter.peek() {
+        match ident.to_string().as_str() {
+            "pub" => {
+                iter.next();
+                if let Some(TokenTree::Group(group)) = iter.peek() {
+                    if group.delimiter() == Delimiter::Parenthesis {
+                        iter.next();
+                    }
+                }
+            }
+            "unsafe" => {
+                iter.next();
+            }
+            _ => break,
         }
+    }
+    let keyword = match iter.next() {
+        Some(TokenTree::Ident(ident)) => ident.to_string(),
+        _ => return false,
+    };
+    let next = iter.next();
+    match keyword.as_str() {
+        "let" | "struct" | "enum" | "trait" | "impl" | "mod" | "use" => true,
+        "union" | "static" => matches!(next, Some(TokenTree::Ident(_))),
+        "extern" => matches!(next, Some(TokenTree::Ident(ident)) if ident.to_string() == "crate"),
+        "macro_rules" => matches!(next, Some(TokenTree::Punct(bang)) if bang.as_char() == '!'),
+        _ => false,
+    }
+}
 
-        // Substitute our variable concatenated onto some prefix, `Prefix~N`.
-        if i + 3 <= tokens.len() {
-            let prefix = match &tokens[i..i + 3] {
-                [first, TokenTree::Punct(tilde), TokenTree::Ident(ident)]
-                    if tilde.as_char() == '~' && ident.to_string() == var.to_string() =>
-                {
-                    match first {
-                        TokenTree::Ident(ident) => Some(ident.clone()),
-                        TokenTree::Group(group) => {
-                            let mut iter = group.stream().into_iter().fuse();
-                            match (iter.next(), iter.next()) {
-                                (Some(TokenTree::Ident(ident)), None) => Some(ident),
-                                _ => None,
-                            }
+// Puts the items first in the body of the first `fn name(...) { ... }`.
+fn insert_in_fn_body(tokens: TokenStream, items: &mut Option<TokenStream>) -> TokenStream {
+    let mut tokens = Vec::from_iter(tokens);
+    for i in 0..tokens.len() {
+        if items.is_none() {
+            break;
+        }
+        match (&tokens[i], tokens.get(i + 1)) {
+            (TokenTree::Ident(keyword), Some(TokenTree::Ident(_)))
+                if keyword.to_string() == "fn" =>
+            {
+                for token in &mut tokens[i + 2..] {
+                    match token {
+                        TokenTree::Punct(punct) if punct.as_char() == ';' => break,
+                        TokenTree::Group(body) if body.delimiter() == Delimiter::Brace => {
+                            let mut stream = items.take().unwrap();
+                            stream.extend(body.stream());
+                            let mut group = Group::new(Delimiter::Brace, stream);
+                            group.set_span(body.span());
+                            *token = TokenTree::Group(group);
+                            break;
                         }
-                        _ => None,
+                        _ => {}
                     }
                 }
-                _ => None,
+            }
+            (TokenTree::Group(group), _) => {
+                let stream = insert_in_fn_body(group.stream(), items);
+                if items.is_none() {
+                    let mut inserted = Group::new(group.delimiter(), stream);
+                    inserted.set_span(group.span());
+                    tokens[i] = TokenTree::Group(inserted);
+                }
+            }
+            _ => {}
+        }
+    }
+    TokenStream::from_iter(tokens)
+}
+
+// const _: &[u8] = include_bytes!("/path/to/regs.csv");
+//
+// Makes Cargo rebuild when a file read by `file(...)` changes.
+fn track_files(files: &[String]) -> TokenStream {
+    let mut tokens = TokenStream::new();
+    for path in files {
+        let item = format!(
+            "const _: &[u8] = include_bytes!({});",
+            Literal::string(path)
+        );
+        tokens.extend(item.parse::<TokenStream>().unwrap());
+    }
+    tokens
+}
+
+// const _: &str = env!("NPROC");
+//
+// Makes Cargo rebuild when an environment variable read by `env!(...)` in a
+// bound changes.
+fn track_env_vars(env_vars: &[String]) -> TokenStream {
+    let mut tokens = TokenStream::new();
+    for name in env_vars {
+        let item = format!("const _: &str = env!({});", Literal::string(name));
+        tokens.extend(item.parse::<TokenStream>().unwrap());
+    }
+    tokens
+}
+
+// The names in `env!("NPROC")` or `env("NPROC")` anywhere in a clause.
+fn find_env_vars(clause: TokenStream, env_vars: &mut Vec<String>) {
+    let tokens = Vec::from_iter(clause);
+    for (i, token) in tokens.iter().enumerate() {
+        let args = match (token, &tokens[i + 1..]) {
+            (TokenTree::Ident(ident), [TokenTree::Punct(bang), TokenTree::Group(args), ..])
+                if ident.to_string() == "env" && bang.as_char() == '!' =>
+            {
+                args
+            }
+            (TokenTree::Ident(ident), [TokenTree::Group(args), ..])
+                if ident.to_string() == "env" =>
+            {
+                args
+            }
+            (TokenTree::Group(group), _) => {
+                find_env_vars(group.stream(), env_vars);
+                continue;
+            }
+            _ => continue,
+        };
+        let mut args = args.stream().into_iter();
+        if let (Some(TokenTree::Literal(lit)), None) = (args.next(), args.next()) {
+            if let Some(name) = parse_string(&lit) {
+                if !env_vars.contains(&name) {
+                    env_vars.push(name);
+                }
+            }
+        }
+    }
+}
+
+// Every combination of values of the variables, in row-major order. Each range
+// may refer to the variables before it, as in `R in 0..4, C in 0..R`.
+fn product(
+    patterns: &[Vec<Ident>],
+    clauses: &[TokenStream],
+    files: &mut Vec<String>,
+) -> Result<(Vec<Vec<Binding>>, Option<Span>), SyntaxError> {
+    let mut rows = vec![Vec::new()];
+    for (n, clause) in clauses.iter().enumerate() {
+        let vars = patterns[..n].concat();
+        let pattern = &patterns[n];
+        let mut next_rows = Vec::new();
+        for row in rows {
+            let iteration = Iteration {
+                vars: &vars,
+                bindings: &row,
+                position: None,
+                nested: false,
             };
-            if let Some(prefix) = prefix {
-                let number = match splice.kind {
-                    Kind::Int => match splice.radix {
-                        Radix::Binary => format!("{0:01$b}", splice.int, splice.width),
-                        Radix::Octal => format!("{0:01$o}", splice.int, splice.width),
-                        Radix::Decimal => format!("{0:01$}", splice.int, splice.width),
-                        Radix::LowerHex => format!("{0:01$x}", splice.int, splice.width),
-                        Radix::UpperHex => format!("{0:01$X}", splice.int, splice.width),
-                    },
-                    Kind::Byte | Kind::Char => {
-                        char::from_u32(splice.int as u32).unwrap().to_string()
-                    }
+            let mut iter = substitute_value(&iteration, clause.clone())?.into_iter();
+            let source = require_source(&mut iter)?;
+            require_end(&mut iter)?;
+            if !mentions(clause.clone(), &vars) {
+                source.check_reversed()?;
+            }
+            let arity = source.arity();
+            if pattern.len() != arity {
+                let message = if arity == 1 {
+                    "expected a single variable".to_owned()
+                } else {
+                    format!("expected a tuple of {} variables", arity)
                 };
-                let concat = format!("{}{}", prefix, number);
-                let ident = Ident::new(&concat, prefix.span());
-                tokens.splice(i..i + 3, iter::once(TokenTree::Ident(ident)));
+                return Err(SyntaxError {
+                    message,
+                    span: pattern[0].span(),
+                });
+            }
+            for element in source.elements(files)? {
+                let mut next_row = row.clone();
+                next_row.extend(element);
+                next_rows.push(next_row);
+            }
+        }
+        rows = next_rows;
+        if rows.is_empty() {
+            let span = clause.clone().into_iter().next().unwrap().span();
+            return Ok((rows, Some(span)));
+        }
+    }
+    Ok((rows, None))
+}
+
+// Reformats the integers of every variable, and of every field of a record
+// variable, according to the `where` clause.
+fn apply_options(rows: &mut [Vec<Binding>], options: &Options) -> Result<(), SyntaxError> {
+    let mut columns = Vec::new();
+    for row in rows {
+        for (i, binding) in row.iter_mut().enumerate() {
+            collect_splices(binding, i.to_string(), &mut columns);
+        }
+    }
+    for (_key, splices) in &mut columns {
+        for splice in splices.iter_mut() {
+            splice.grouping.paste = options.paste_groups;
+            if let Some(radix) = options.radix {
+                splice.radix = radix;
+            }
+            if let Some((suffix, span)) = &options.suffix {
+                splice.kind.check(splice.int, suffix, *span)?;
+                splice.suffix = suffix.clone();
+            }
+        }
+        let width = match options.pad {
+            Some(Pad::Auto) => splices
+                .iter()
+                .map(|splice| {
+                    Splice {
+                        width: 0,
+                        ..Splice::clone(splice)
+                    }
+                    .digits()
+                    .len()
+                })
+                .max(),
+            Some(Pad::Width(width)) => Some(width),
+            None => None,
+        };
+        if let Some(width) = width {
+            for splice in splices {
+                splice.width = width;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Groups the integer splices of all rows by variable, or by `R.field`.
+fn collect_splices<'a>(
+    binding: &'a mut Binding,
+    key: String,
+    columns: &mut Vec<(String, Vec<&'a mut Splice>)>,
+) {
+    match binding {
+        Binding::Int(splice) if splice.kind == Kind::Int => {
+            match columns.iter().position(|(column, _splices)| *column == key) {
+                Some(i) => columns[i].1.push(splice),
+                None => columns.push((key, vec![splice])),
+            }
+        }
+        Binding::Int(_) | Binding::Tokens(_) => {}
+        Binding::Record(record) => {
+            for (name, field) in &mut record.fields {
+                collect_splices(field, format!("{}.{}", key, name), columns);
+            }
+        }
+    }
+}
+
+// Whether a clause refers to any of the given variables.
+fn mentions(tokens: TokenStream, vars: &[Ident]) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => vars.iter().any(|var| var.to_string() == ident.to_string()),
+        TokenTree::Group(group) => mentions(group.stream(), vars),
+        TokenTree::Punct(_) | TokenTree::Literal(_) => false,
+    })
+}
+
+// The substitutions for one repetition of the body.
+struct Iteration<'a> {
+    vars: &'a [Ident],
+    bindings: &'a [Binding],
+    // None while substituting earlier variables into a later range.
+    position: Option<Position>,
+    // Inside the body of a nested `seq!(...)`.
+    nested: bool,
+}
+
+#[derive(Copy, Clone)]
+struct Position {
+    index: usize,
+    len: usize,
+}
+
+fn iterations<'a>(
+    vars: &'a [Ident],
+    rows: &'a [Vec<Binding>],
+) -> impl Iterator<Item = Iteration<'a>> {
+    let len = rows.len();
+    rows.iter()
+        .enumerate()
+        .map(move |(index, bindings)| Iteration {
+            vars,
+            bindings,
+            position: Some(Position { index, len }),
+            nested: false,
+        })
+}
+
+impl<'a> Iteration<'a> {
+    // Looks up a variable `R` or a field of a record `R.addr`.
+    fn binding(&self, name: &str) -> Option<&'a Binding> {
+        let (var, field) = match name.split_once('.') {
+            Some((var, field)) => (var, Some(field)),
+            None => (name, None),
+        };
+        let i = self.vars.iter().position(|v| v.to_string() == var)?;
+        match (&self.bindings[i], field) {
+            (binding, None) => Some(binding),
+            (Binding::Record(record), Some(field)) => record.field(field),
+            (Binding::Int(_) | Binding::Tokens(_), Some(_)) => None,
+        }
+    }
+
+    fn splice(&self, name: &str) -> Option<&'a Splice> {
+        match self.binding(name)? {
+            Binding::Int(splice) => Some(splice),
+            Binding::Tokens(_) | Binding::Record(_) => None,
+        }
+    }
+
+    // Recognizes `R.addr` where `R` is one of our record variables.
+    fn field(&self, tokens: &[TokenTree]) -> Result<Option<&'a Binding>, SyntaxError> {
+        let [TokenTree::Ident(var), TokenTree::Punct(dot), TokenTree::Ident(field), ..] = tokens
+        else {
+            return Ok(None);
+        };
+        let Some(Binding::Record(record)) = self.binding(&var.to_string()) else {
+            return Ok(None);
+        };
+        if dot.as_char() != '.' || dot.spacing() != Spacing::Alone {
+            return Ok(None);
+        }
+        match record.field(&field.to_string()) {
+            Some(binding) => Ok(Some(binding)),
+            None => Err(SyntaxError {
+                message: format!("record has no field `{}`", field),
+                span: record.span,
+            }),
+        }
+    }
+
+    // Whether a name in an expression belongs to this invocation. Inside a
+    // nested seq!, metavariables belong to the inner one.
+    fn knows(&self, name: &str) -> bool {
+        match name.strip_prefix('#') {
+            Some(_meta) => !self.nested,
+            None => self.binding(name).is_some(),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Const> {
+        if let (false, Some(Position { index, len })) = (self.nested, self.position) {
+            match name {
+                "#index" => return Some(Const::Int(Int::new(false, index as u128))),
+                "#len" => return Some(Const::Int(Int::new(false, len as u128))),
+                "#first" => return Some(Const::Bool(index == 0)),
+                "#last" => return Some(Const::Bool(index + 1 == len)),
+                _ => {}
+            }
+        }
+        self.splice(name).map(|splice| Const::Int(splice.int))
+    }
+}
+
+fn repeat(
+    vars: &[Ident],
+    rows: &[Vec<Binding>],
+    body: &TokenStream,
+) -> Result<TokenStream, SyntaxError> {
+    let mut repeated = TokenStream::new();
+    for iteration in iterations(vars, rows) {
+        repeated.extend(substitute_value(&iteration, body.clone())?);
+    }
+    Ok(repeated)
+}
+
+fn substitute_value(iteration: &Iteration, body: TokenStream) -> Result<TokenStream, SyntaxError> {
+    let mut tokens = Vec::from_iter(body);
+
+    let mut i = 0;
+    while i < tokens.len() {
+        // Substitute a chain of identifiers, literals, and our variables joined
+        // by `~` into one identifier, as in `Prefix~N` or `reg~N~_ctrl`.
+        if let Some((ident, len)) = paste(iteration, &tokens[i..])? {
+            tokens.splice(i..i + len, iter::once(TokenTree::Ident(ident)));
+            i += 1;
+            continue;
+        }
+
+        // Substitute a field of our record variable, e.g. `R.addr`, or our
+        // variable by itself, e.g. `N`.
+        let var = match iteration.field(&tokens[i..])? {
+            Some(binding) => Some((binding, 3)),
+            None => match &tokens[i] {
+                TokenTree::Ident(ident) => iteration.binding(&ident.to_string()).map(|b| (b, 1)),
+                _ => None,
+            },
+        };
+        if let Some((binding, len)) = var {
+            let original_span = tokens[i].span();
+            if let Binding::Record(_) = binding {
+                return Err(SyntaxError {
+                    message: format!("expected a field of `{}`, as in `{0}.name`", tokens[i]),
+                    span: original_span,
+                });
+            }
+            let replacement = binding.tokens(original_span);
+            let replacement = operand(replacement, tokens.get(i + len));
+            let replacement_len = replacement.len();
+            tokens.splice(i..i + len, replacement);
+            i += replacement_len;
+            continue;
+        }
+
+        // Substitute the selected branch of `#if N > 0 { ... } #else { ... }`.
+        if let Some((content, len)) = conditional(iteration, &tokens[i..])? {
+            let content = Vec::from_iter(content);
+            let content_len = content.len();
+            tokens.splice(i..i + len, content);
+            i += content_len;
+            continue;
+        }
+
+        // Substitute iteration metadata, `#index`, `#len`, `#first`, `#last`.
+        if let [TokenTree::Punct(pound), TokenTree::Ident(ident), ..] = &tokens[i..] {
+            let meta = if pound.as_char() == '#' {
+                iteration.lookup(&format!("#{}", ident))
+            } else {
+                None
+            };
+            if let Some(meta) = meta {
+                let original_span = ident.span();
+                tokens.splice(i..i + 2, iter::once(const_token(meta, original_span)));
                 i += 1;
                 continue;
             }
         }
 
-        // Recursively substitute content nested in a group.
+        // Substitute an expression of our variables, `#{N * 8}`, optionally
+        // formatted or converted as in `#{N:02x}` or `#{N:char}`.
+        if let Some((group, expr, modifier)) = enter_interpolation(iteration, &tokens[i..]) {
+            let original_span = group.span();
+            let replacement = match modifier {
+                Some(Modifier::Conversion(conversion, conversion_span)) => {
+                    convert(iteration, expr, conversion, original_span, conversion_span)?
+                }
+                Some(Modifier::Format(format)) => {
+                    let (value, splice) = interpolate(iteration, expr, original_span)?;
+                    let int = value.to_int(original_span)?;
+                    let splice = splice.with_int(int, original_span)?;
+                    splice.formatted(format).tokens(original_span)
+                }
+                None => match interpolate(iteration, expr, original_span)? {
+                    (Const::Int(int), format) => {
+                        format.with_int(int, original_span)?.tokens(original_span)
+                    }
+                    (value @ Const::Bool(_), _format) => vec![const_token(value, original_span)],
+                },
+            };
+            let replacement = operand(replacement, tokens.get(i + 2));
+            let replacement_len = replacement.len();
+            tokens.splice(i..i + 2, replacement);
+            i += replacement_len;
+            continue;
+        }
+
+        // Recursively substitute content nested in a group. The body of a
+        // nested `seq!(...)` keeps what only the inner invocation can evaluate.
+        let nested = is_nested_seq(&tokens[..i]);
         if let TokenTree::Group(group) = &mut tokens[i] {
             let original_span = group.span();
-            let content = substitute_value(var, splice, group.stream());
+            let content = if nested {
+                let inner = Iteration {
+                    nested: true,
+                    ..*iteration
+                };
+                substitute_value(&inner, group.stream())?
+            } else {
+                substitute_value(iteration, group.stream())?
+            };
             *group = Group::new(group.delimiter(), content);
             group.set_span(original_span);
         }
@@ -277,78 +1217,754 @@ fn substitute_value(var: &Ident, splice: &Splice, body: TokenStream) -> TokenStr
         i += 1;
     }
 
-    TokenStream::from_iter(tokens)
+    Ok(TokenStream::from_iter(tokens))
 }
 
-fn enter_repetition(tokens: &[TokenTree]) -> Option<TokenStream> {
-    assert_eq!(tokens.len(), 3);
-    match &tokens[0] {
-        TokenTree::Punct(punct) if punct.as_char() == '#' => {}
+// Recognizes the `:char` after a variable.
+fn conversion(tokens: &[TokenTree]) -> Option<(Conversion, Span)> {
+    let [TokenTree::Punct(colon), TokenTree::Ident(ident), ..] = tokens else {
+        return None;
+    };
+    if colon.as_char() != ':' || colon.spacing() != Spacing::Alone {
+        return None;
+    }
+    let conversion = match ident.to_string().as_str() {
+        "byte" => Conversion::Byte,
+        "char" => Conversion::Char,
+        "str" => Conversion::Str,
+        "int" => Conversion::Int,
+        "bool" => Conversion::Bool,
+        "float" => Conversion::Float,
         _ => return None,
+    };
+    Some((conversion, ident.span()))
+}
+
+// After a pasted variable, only a spec with both a width and a radix, like
+// `:02x`, is recognized. Any other could be a type or value, as in
+// `field~N: X`, and is written `~{N:X}` instead.
+fn pasted_format_spec(tokens: &[TokenTree]) -> Option<Format> {
+    match tokens {
+        [_colon, TokenTree::Literal(lit), ..] if lit.to_string().ends_with(char::is_alphabetic) => {
+            format_spec(tokens)
+        }
+        _ => None,
+    }
+}
+
+// Recognizes the `:02x` after a variable: an optional zero-padded width and
+// then one of `x`, `X`, `o`, `b`, `d`.
+fn format_spec(tokens: &[TokenTree]) -> Option<Format> {
+    let [TokenTree::Punct(colon), spec, ..] = tokens else {
+        return None;
+    };
+    if colon.as_char() != ':' || colon.spacing() != Spacing::Alone {
+        return None;
     }
-    match &tokens[2] {
-        TokenTree::Punct(punct) if punct.as_char() == '*' => {}
+    let repr = match spec {
+        TokenTree::Ident(ident) => ident.to_string(),
+        // A width must be written with its leading zero, as in `02`.
+        TokenTree::Literal(lit) => {
+            let repr = lit.to_string();
+            let digits = repr.bytes().take_while(u8::is_ascii_digit).count();
+            if digits < 2 || !repr.starts_with('0') {
+                return None;
+            }
+            repr
+        }
         _ => return None,
+    };
+    let digits = repr.bytes().take_while(u8::is_ascii_digit).count();
+    let width = match digits {
+        0 => 0,
+        _ => repr[..digits].parse().ok()?,
+    };
+    let radix = match &repr[digits..] {
+        "b" => Radix::Binary,
+        "o" => Radix::Octal,
+        "" | "d" => Radix::Decimal,
+        "x" => Radix::LowerHex,
+        "X" => Radix::UpperHex,
+        _ => return None,
+    };
+    Some(Format { radix, width })
+}
+
+// Returns the substituted tokens of the selected branch, possibly empty, and
+// the number of tokens consumed by the whole `#if ... #else if ... #else ...`
+// chain.
+fn conditional(
+    iteration: &Iteration,
+    tokens: &[TokenTree],
+) -> Result<Option<(TokenStream, usize)>, SyntaxError> {
+    let Some((branches, len)) = require_chain(tokens)? else {
+        return Ok(None);
+    };
+
+    // Inside a nested seq!, a chain whose conditions name the inner variables
+    // is left for the inner invocation to select.
+    let ours = branches.iter().all(|(condition, _branch)| match condition {
+        Some(condition) => condition.names_known(&|name| iteration.knows(name)),
+        None => true,
+    });
+    if iteration.nested && !ours {
+        return Ok(Some((substitute_chain(iteration, &tokens[..len])?, len)));
     }
-    match &tokens[1] {
-        TokenTree::Group(group) if group.delimiter() == Delimiter::Parenthesis => {
-            Some(group.stream())
+
+    let mut selected = None;
+    for (condition, branch) in branches {
+        let value = match condition {
+            Some(condition) => {
+                let value = condition.eval(&|name| iteration.lookup(name))?;
+                value.to_bool(condition.span())?
+            }
+            None => true,
+        };
+        if value && selected.is_none() {
+            selected = Some(branch);
+        }
+    }
+    let content = substitute_value(iteration, selected.unwrap_or_default())?;
+    Ok(Some((content, len)))
+}
+
+// A condition and its branch. The final `#else { ... }` has no condition.
+type Branch = (Option<Expr>, TokenStream);
+
+// The branches of a `#if` chain and the number of tokens in the chain.
+fn require_chain(tokens: &[TokenTree]) -> Result<Option<(Vec<Branch>, usize)>, SyntaxError> {
+    if !enter_keyword(tokens, "if") {
+        return Ok(None);
+    }
+
+    let mut iter = TokenStream::from_iter(tokens[2..].iter().cloned()).into_iter();
+    let condition = require_expr(&mut iter)?;
+    let then_branch = require_braces(&mut iter)?;
+    let mut len = tokens.len() - iter.count();
+    let mut branches = vec![(Some(condition), then_branch)];
+
+    if enter_keyword(&tokens[len..], "else") {
+        len += 2;
+        if let Some((else_branches, else_len)) = require_chain(&tokens[len..])? {
+            branches.extend(else_branches);
+            len += else_len;
+        } else {
+            let else_branch = match tokens.get(len) {
+                Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => {
+                    group.stream()
+                }
+                token => {
+                    let token = token.unwrap_or(&tokens[len - 1]);
+                    return Err(syntax(token, "expected curly braces"));
+                }
+            };
+            branches.push((None, else_branch));
+            len += 1;
+        }
+    }
+
+    Ok(Some((branches, len)))
+}
+
+// Substitutes our variables into the conditions and branches of a chain left
+// for a nested seq!, keeping its `#if` and `#else` keywords.
+fn substitute_chain(
+    iteration: &Iteration,
+    chain: &[TokenTree],
+) -> Result<TokenStream, SyntaxError> {
+    let mut substituted = TokenStream::new();
+    let mut run = Vec::new();
+    let mut i = 0;
+    while i < chain.len() {
+        if enter_keyword(&chain[i..], "if") || enter_keyword(&chain[i..], "else") {
+            let tokens = TokenStream::from_iter(run.drain(..));
+            substituted.extend(substitute_value(iteration, tokens)?);
+            substituted.extend(chain[i..i + 2].iter().cloned());
+            i += 2;
+        } else {
+            run.push(chain[i].clone());
+            i += 1;
+        }
+    }
+    substituted.extend(substitute_value(iteration, TokenStream::from_iter(run))?);
+    Ok(substituted)
+}
+
+// `#if` or `#else`
+fn enter_keyword(tokens: &[TokenTree], keyword: &str) -> bool {
+    match tokens {
+        [TokenTree::Punct(pound), TokenTree::Ident(ident), ..] => {
+            pound.as_char() == '#' && ident.to_string() == keyword
+        }
+        _ => false,
+    }
+}
+
+// Metavariables are emitted as unsuffixed integers or as `true`/`false`.
+fn const_token(value: Const, span: Span) -> TokenTree {
+    match value {
+        Const::Int(int) => {
+            let mut literal = Literal::u128_unsuffixed(int.magnitude());
+            literal.set_span(span);
+            TokenTree::Literal(literal)
+        }
+        Const::Bool(bool) => {
+            TokenTree::Ident(Ident::new(if bool { "true" } else { "false" }, span))
+        }
+    }
+}
+
+enum Fragment<'a> {
+    Text(String),
+    Var(Splice),
+    Tokens(&'a TokenStream),
+    Expr(Group),
+}
+
+fn paste(
+    iteration: &Iteration,
+    tokens: &[TokenTree],
+) -> Result<Option<(Ident, usize)>, SyntaxError> {
+    let mut fragments = Vec::new();
+    let mut len = 0;
+    while let Some((mut fragment, fragment_len)) = paste_fragment(iteration, &tokens[len..])? {
+        len += fragment_len;
+        // A format spec is only recognized after a `~`, as in `Pin~N:02x`. On
+        // the first fragment it could be ordinary Rust, as in `Tup { N: x }`.
+        if let (false, Fragment::Var(splice), Some(format)) = (
+            fragments.is_empty(),
+            &fragment,
+            pasted_format_spec(&tokens[len..]),
+        ) {
+            fragment = Fragment::Var(splice.formatted(format));
+            len += 2;
+        }
+        fragments.push(fragment);
+        match tokens.get(len) {
+            Some(TokenTree::Punct(tilde))
+                if tilde.as_char() == '~'
+                    && paste_fragment(iteration, &tokens[len + 1..])?.is_some() =>
+            {
+                len += 1;
+            }
+            _ => break,
+        }
+    }
+
+    // Chains that do not involve our variables are left for an inner seq! to
+    // paste, as are those with an expression for it to evaluate.
+    let substituted = fragments
+        .iter()
+        .any(|fragment| !matches!(fragment, Fragment::Text(_)));
+    let inner = fragments.iter().any(|fragment| match fragment {
+        Fragment::Expr(group) => !is_ours(iteration, &split_modifier(group).0),
+        _ => false,
+    });
+    if fragments.len() < 2 || !substituted || inner {
+        return Ok(None);
+    }
+
+    let mut concat = String::new();
+    for fragment in fragments {
+        match fragment {
+            Fragment::Text(text) => concat += &text,
+            Fragment::Var(splice) => concat += &splice.paste(),
+            Fragment::Tokens(tokens) => {
+                let mut iter = tokens.clone().into_iter();
+                match (iter.next(), iter.next()) {
+                    (Some(TokenTree::Ident(ident)), None) => concat += &ident.to_string(),
+                    (Some(token), _) => {
+                        return Err(SyntaxError {
+                            message: "only a single identifier or integer can be pasted".to_owned(),
+                            span: token.span(),
+                        });
+                    }
+                    (None, _) => unreachable!(),
+                }
+            }
+            Fragment::Expr(group) => {
+                let (expr, modifier) = split_modifier(&group);
+                let (value, format) = interpolate(iteration, expr, group.span())?;
+                let int = value.to_int(group.span())?;
+                let splice = format.with_int(int, group.span())?;
+                concat += &match modifier {
+                    Some(Modifier::Format(format)) => splice.formatted(format).paste(),
+                    Some(Modifier::Conversion(_, span)) => {
+                        return Err(SyntaxError {
+                            message: "only a format spec can be applied when pasting".to_owned(),
+                            span,
+                        });
+                    }
+                    None => splice.paste(),
+                };
+            }
+        }
+    }
+
+    let span = tokens[0].span();
+    if concat.starts_with(|ch: char| ch.is_ascii_digit()) {
+        return Err(SyntaxError {
+            message: format!("pasted identifier `{}` cannot start with a digit", concat),
+            span,
+        });
+    }
+    // Pasted characters like `'\n'` or `'-'` cannot be part of an identifier,
+    // and Ident::new would panic.
+    let mut lexed = concat.parse::<TokenStream>().into_iter().flatten();
+    let is_ident = match (lexed.next(), lexed.next()) {
+        (Some(TokenTree::Ident(ident)), None) => ident.to_string() == concat,
+        _ => false,
+    };
+    if !is_ident {
+        let message = match concat
+            .chars()
+            .find(|ch| !ch.is_alphanumeric() && *ch != '_')
+        {
+            Some(ch) => format!("cannot paste {:?} into an identifier", ch),
+            None => format!("pasted identifier `{}` is not valid", concat.escape_debug()),
+        };
+        return Err(SyntaxError { message, span });
+    }
+    Ok(Some((Ident::new(&concat, span), len)))
+}
+
+// Returns the fragment and the number of tokens it spans.
+fn paste_fragment<'a>(
+    iteration: &Iteration<'a>,
+    tokens: &[TokenTree],
+) -> Result<Option<(Fragment<'a>, usize)>, SyntaxError> {
+    if let Some(binding) = iteration.field(tokens)? {
+        return Ok(Fragment::from_binding(binding).map(|fragment| (fragment, 3)));
+    }
+    let fragment = match tokens.first() {
+        Some(TokenTree::Ident(ident)) => {
+            let name = ident.to_string();
+            match iteration.binding(&name) {
+                Some(binding) => Fragment::from_binding(binding),
+                None => Some(Fragment::Text(name)),
+            }
+        }
+        Some(TokenTree::Literal(lit)) => {
+            let repr = lit.to_string();
+            if repr.chars().all(|ch| ch.is_alphanumeric() || ch == '_') {
+                Some(Fragment::Text(repr))
+            } else {
+                None
+            }
+        }
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => {
+            Some(Fragment::Expr(group.clone()))
+        }
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::None => {
+            let mut iter = group.stream().into_iter().fuse();
+            match (iter.next(), iter.next()) {
+                (Some(token), None) => paste_fragment(iteration, &[token])?.map(|(f, _)| f),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    Ok(fragment.map(|fragment| (fragment, 1)))
+}
+
+impl<'a> Fragment<'a> {
+    // A record is only pasteable through one of its fields, `get_~R.name`.
+    fn from_binding(binding: &'a Binding) -> Option<Self> {
+        match binding {
+            Binding::Int(splice) => Some(Fragment::Var(splice.clone())),
+            Binding::Tokens(tokens) => Some(Fragment::Tokens(tokens)),
+            Binding::Record(_) => None,
+        }
+    }
+}
+
+fn enter_interpolation<'a>(
+    iteration: &Iteration,
+    tokens: &'a [TokenTree],
+) -> Option<(&'a Group, Vec<TokenTree>, Option<Modifier>)> {
+    match tokens {
+        [TokenTree::Punct(punct), TokenTree::Group(group), ..]
+            if punct.as_char() == '#' && group.delimiter() == Delimiter::Brace =>
+        {
+            let (expr, modifier) = split_modifier(group);
+            if is_ours(iteration, &expr) {
+                Some((group, expr, modifier))
+            } else {
+                None
+            }
         }
         _ => None,
     }
 }
 
+// Inside a nested seq!, an expression naming anything besides our variables is
+// left for the inner invocation, as in `#{J * 2}` where `J` is its variable.
+fn is_ours(iteration: &Iteration, expr: &[TokenTree]) -> bool {
+    if !iteration.nested {
+        return true;
+    }
+    let mut iter = TokenStream::from_iter(expr.iter().cloned()).into_iter();
+    match require_expr(&mut iter) {
+        Ok(expr) => expr.names_known(&|name| iteration.knows(name)),
+        // Reported when the expression is evaluated.
+        Err(_) => true,
+    }
+}
+
+// `seq!` or `seq_macro::seq!` right before a group.
+fn is_nested_seq(preceding: &[TokenTree]) -> bool {
+    match preceding {
+        [.., TokenTree::Ident(ident), TokenTree::Punct(bang)] => {
+            ident.to_string() == "seq" && bang.as_char() == '!'
+        }
+        _ => false,
+    }
+}
+
+// Separates a trailing `:02x` or `:char` from the expression in `#{...}`.
+fn split_modifier(group: &Group) -> (Vec<TokenTree>, Option<Modifier>) {
+    let mut tokens = Vec::from_iter(group.stream());
+    let len = tokens.len();
+    if len >= 3 {
+        // Not the end of a path like `u8::MAX`.
+        let path = matches!(&tokens[len - 3], TokenTree::Punct(punct) if punct.as_char() == ':');
+        let modifier = match format_spec(&tokens[len - 2..]) {
+            Some(format) => Some(Modifier::Format(format)),
+            None => conversion(&tokens[len - 2..])
+                .map(|(conversion, span)| Modifier::Conversion(conversion, span)),
+        };
+        if let (false, Some(modifier)) = (path, modifier) {
+            tokens.truncate(len - 2);
+            return (tokens, Some(modifier));
+        }
+    }
+    (tokens, None)
+}
+
+// `#{N:char}`. Only `str` applies to a variable bound to arbitrary tokens.
+fn convert(
+    iteration: &Iteration,
+    expr: Vec<TokenTree>,
+    conversion: Conversion,
+    span: Span,
+    conversion_span: Span,
+) -> Result<Vec<TokenTree>, SyntaxError> {
+    let binding = match expr.as_slice() {
+        [TokenTree::Ident(ident)] => iteration.binding(&ident.to_string()),
+        [_, _, _] => iteration.field(&expr)?,
+        _ => None,
+    };
+    if let (Some(Binding::Tokens(tokens)), Conversion::Str) = (binding, conversion) {
+        let mut literal = Literal::string(&tokens.to_string());
+        literal.set_span(span);
+        return Ok(vec![TokenTree::Literal(literal)]);
+    }
+    let (value, format) = interpolate(iteration, expr, span)?;
+    let int = value.to_int(span)?;
+    format
+        .with_int(int, span)?
+        .convert(conversion, span, conversion_span)
+}
+
+// Evaluates the expression inside `#{...}` or `Prefix~{...}`. An integer result
+// is formatted like the first variable in the expression.
+fn interpolate(
+    iteration: &Iteration,
+    expr: Vec<TokenTree>,
+    span: Span,
+) -> Result<(Const, Splice), SyntaxError> {
+    let mut iter = TokenStream::from_iter(expr).into_iter();
+    if iter.clone().next().is_none() {
+        return Err(SyntaxError {
+            message: "expected expression".to_owned(),
+            span,
+        });
+    }
+    let expr = require_expr(&mut iter)?;
+    require_end(&mut iter)?;
+    let value = expr.eval(&|name| iteration.lookup(name))?;
+    let format = match expr.first_var() {
+        Some(var) => iteration.splice(&var).cloned(),
+        None => None,
+    };
+    Ok((value, format.unwrap_or_else(Splice::unformatted)))
+}
+
+// Recognizes `#(...)*`, or `#(...),*` with a separator which, as in
+// `macro_rules!`, may be a run of joint punctuation like `&&` or `=>`. Returns
+// the template, the separator, and the number of tokens consumed.
+fn enter_repetition(tokens: &[TokenTree]) -> Option<(TokenStream, Vec<Punct>, usize)> {
+    let [TokenTree::Punct(pound), TokenTree::Group(group), rest @ ..] = tokens else {
+        return None;
+    };
+    if pound.as_char() != '#' || group.delimiter() != Delimiter::Parenthesis {
+        return None;
+    }
+    let mut separator = Vec::<Punct>::new();
+    for token in rest {
+        let TokenTree::Punct(punct) = token else {
+            return None;
+        };
+        if punct.as_char() == '*' {
+            if let Some(last) = separator.last_mut() {
+                // Joint with the `*`, but emitted standalone between
+                // repetitions.
+                let mut alone = Punct::new(last.as_char(), Spacing::Alone);
+                alone.set_span(last.span());
+                *last = alone;
+            }
+            let len = 3 + separator.len();
+            return Some((group.stream(), separator, len));
+        }
+        if let Some(last) = separator.last() {
+            if last.spacing() != Spacing::Joint {
+                return None;
+            }
+        }
+        separator.push(punct.clone());
+    }
+    None
+}
+
 fn expand_repetitions(
-    var: &Ident,
-    range: &Range,
+    vars: &[Ident],
+    rows: &[Vec<Binding>],
     body: TokenStream,
     found_repetition: &mut bool,
-) -> TokenStream {
+) -> Result<TokenStream, SyntaxError> {
     let mut tokens = Vec::from_iter(body);
 
-    // Look for `#(...)*`.
+    // Look for `#(...)*` or `#(...),*`.
     let mut i = 0;
     while i < tokens.len() {
         if let TokenTree::Group(group) = &mut tokens[i] {
-            let content = expand_repetitions(var, range, group.stream(), found_repetition);
+            let content = expand_repetitions(vars, rows, group.stream(), found_repetition)?;
             let original_span = group.span();
             *group = Group::new(group.delimiter(), content);
             group.set_span(original_span);
             i += 1;
             continue;
         }
-        if i + 3 > tokens.len() {
-            i += 1;
-            continue;
+        // `#len` is also meaningful outside of the repetition.
+        if let [TokenTree::Punct(pound), TokenTree::Ident(ident), ..] = &tokens[i..] {
+            if pound.as_char() == '#' && ident.to_string() == "len" {
+                let len = Const::Int(Int::new(false, rows.len() as u128));
+                let token = const_token(len, ident.span());
+                tokens.splice(i..i + 2, iter::once(token));
+                i += 1;
+                continue;
+            }
         }
-        let Some(template) = enter_repetition(&tokens[i..i + 3]) else {
+        let Some((template, separator, len)) = enter_repetition(&tokens[i..]) else {
             i += 1;
             continue;
         };
         *found_repetition = true;
         let mut repeated = Vec::new();
-        for value in range {
-            repeated.extend(substitute_value(var, &value, template.clone()));
+        for (index, iteration) in iterations(vars, rows).enumerate() {
+            if index > 0 {
+                repeated.extend(separator.iter().cloned().map(TokenTree::Punct));
+            }
+            repeated.extend(substitute_value(&iteration, template.clone())?);
         }
         let repeated_len = repeated.len();
-        tokens.splice(i..i + 3, repeated);
+        tokens.splice(i..i + len, repeated);
         i += repeated_len;
     }
 
-    TokenStream::from_iter(tokens)
+    Ok(TokenStream::from_iter(tokens))
+}
+
+impl Grouping {
+    // From the number of digits between each underscore, left to right.
+    fn from_groups(mut groups: Vec<usize>) -> Self {
+        groups.retain(|&len| len != 0);
+        let mut sizes = Vec::from_iter(groups.iter().skip(1).rev().copied());
+        while sizes.len() >= 2 && sizes[sizes.len() - 1] == sizes[sizes.len() - 2] {
+            sizes.pop();
+        }
+        Grouping {
+            sizes,
+            paste: false,
+        }
+    }
+
+    fn apply(&self, digits: &str) -> String {
+        let Some(&last) = self.sizes.last() else {
+            return digits.to_owned();
+        };
+        let mut sizes = self.sizes.iter().copied().chain(iter::repeat(last));
+        let mut groups = Vec::new();
+        let mut end = digits.len();
+        while end > 0 {
+            let start = end.saturating_sub(sizes.next().unwrap());
+            groups.push(&digits[start..end]);
+            end = start;
+        }
+        groups.reverse();
+        groups.join("_")
+    }
 }
 
-impl Splice<'_> {
+impl Splice {
+    // Formatting for values computed from literals only.
+    fn unformatted() -> Self {
+        Splice {
+            int: Int::new(false, 0),
+            kind: Kind::Int,
+            suffix: String::new(),
+            width: 0,
+            radix: Radix::Decimal,
+            grouping: Grouping::default(),
+        }
+    }
+
+    // The same formatting applied to a computed value.
+    fn with_int(&self, int: Int, span: Span) -> Result<Splice, SyntaxError> {
+        self.kind.check(int, &self.suffix, span)?;
+        Ok(Splice {
+            int,
+            ..self.clone()
+        })
+    }
+
+    // Text pasted onto the end of an identifier. Negative values are pasted as
+    // `Neg3` since `-` can't appear in an identifier.
+    fn paste(&self) -> String {
+        match self.kind {
+            Kind::Int => {
+                let sign = if self.int.is_negative() { "Neg" } else { "" };
+                let digits = if self.grouping.paste {
+                    self.grouping.apply(&self.digits())
+                } else {
+                    self.digits()
+                };
+                format!("{}{}", sign, digits)
+            }
+            Kind::Byte | Kind::Char => char::from_u32(self.int.to_u32().unwrap())
+                .unwrap()
+                .to_string(),
+        }
+    }
+
+    // The magnitude in our radix, zero-padded to our width.
+    fn digits(&self) -> String {
+        let magnitude = self.int.magnitude();
+        let width = self.width;
+        match self.radix {
+            Radix::Binary => format!("{:01$b}", magnitude, width),
+            Radix::Octal => format!("{:01$o}", magnitude, width),
+            Radix::Decimal => format!("{:01$}", magnitude, width),
+            Radix::LowerHex => format!("{:01$x}", magnitude, width),
+            Radix::UpperHex => format!("{:01$X}", magnitude, width),
+        }
+    }
+
+    // The value as an integer with the radix and width of a format spec.
+    fn formatted(&self, format: Format) -> Splice {
+        Splice {
+            kind: Kind::Int,
+            suffix: match self.kind {
+                Kind::Int => self.suffix.clone(),
+                Kind::Byte | Kind::Char => String::new(),
+            },
+            width: format.width,
+            radix: format.radix,
+            grouping: Grouping::default(),
+            ..self.clone()
+        }
+    }
+
+    // The value as a literal of a different kind, for `N:char` and friends.
+    fn convert(
+        &self,
+        conversion: Conversion,
+        span: Span,
+        conversion_span: Span,
+    ) -> Result<Vec<TokenTree>, SyntaxError> {
+        let unrepresentable = |kind: &str| SyntaxError {
+            message: format!("{} is not representable as {}", self.int, kind),
+            span: conversion_span,
+        };
+        let mut literal = match conversion {
+            Conversion::Byte => {
+                let byte = self.int.to_u32().filter(|byte| *byte <= 0xFF);
+                let byte = byte.ok_or_else(|| unrepresentable("a byte"))? as u8;
+                let repr = format!("b'{}'", ascii::escape_default(byte));
+                match repr.parse::<TokenStream>().unwrap().into_iter().next() {
+                    Some(TokenTree::Literal(literal)) => literal,
+                    _ => unreachable!(),
+                }
+            }
+            Conversion::Char => {
+                let ch = self.int.to_u32().and_then(char::from_u32);
+                Literal::character(ch.ok_or_else(|| unrepresentable("a char"))?)
+            }
+            Conversion::Str => Literal::string(&self.text()),
+            Conversion::Int => {
+                let format = Format {
+                    radix: self.radix,
+                    width: self.width,
+                };
+                let splice = Splice {
+                    grouping: self.grouping.clone(),
+                    ..self.formatted(format)
+                };
+                return Ok(splice.tokens(span));
+            }
+            Conversion::Bool => {
+                let bool = match (self.int.is_negative(), self.int.magnitude()) {
+                    (false, 0) => "false",
+                    (false, 1) => "true",
+                    _ => return Err(unrepresentable("a bool")),
+                };
+                return Ok(vec![TokenTree::Ident(Ident::new(bool, span))]);
+            }
+            Conversion::Float => {
+                // Integers beyond 2^53 are not all exactly representable.
+                if self.int.magnitude() > 1 << 53 {
+                    return Err(unrepresentable("a float"));
+                }
+                Literal::f64_unsuffixed(self.int.magnitude() as f64)
+            }
+        };
+        literal.set_span(span);
+        let negative = self.int.is_negative() && conversion != Conversion::Str;
+        Ok(vec![signed(negative, literal, span)])
+    }
+
+    // Contents of the string produced by `N:str`.
+    fn text(&self) -> String {
+        match self.kind {
+            Kind::Int if self.int.is_negative() => {
+                let magnitude = Splice {
+                    int: self.int.neg(),
+                    ..self.clone()
+                };
+                format!("-{}", magnitude.paste())
+            }
+            Kind::Int | Kind::Byte | Kind::Char => self.paste(),
+        }
+    }
+
+    fn tokens(&self, span: Span) -> Vec<TokenTree> {
+        let mut literal = self.literal();
+        literal.set_span(span);
+        vec![signed(self.int.is_negative(), literal, span)]
+    }
+
+    // Literal of the magnitude, without sign.
     fn literal(&self) -> Literal {
         match self.kind {
             Kind::Int | Kind::Byte => {
-                let repr = match self.radix {
-                    Radix::Binary => format!("0b{0:02$b}{1}", self.int, self.suffix, self.width),
-                    Radix::Octal => format!("0o{0:02$o}{1}", self.int, self.suffix, self.width),
-                    Radix::Decimal => format!("{0:02$}{1}", self.int, self.suffix, self.width),
-                    Radix::LowerHex => format!("0x{0:02$x}{1}", self.int, self.suffix, self.width),
-                    Radix::UpperHex => format!("0x{0:02$X}{1}", self.int, self.suffix, self.width),
+                let prefix = match self.radix {
+                    Radix::Binary => "0b",
+                    Radix::Octal => "0o",
+                    Radix::Decimal => "",
+                    Radix::LowerHex | Radix::UpperHex => "0x",
                 };
+                let digits = self.grouping.apply(&self.digits());
+                let repr = format!("{}{}{}", prefix, digits, self.suffix);
                 let tokens = repr.parse::<TokenStream>().unwrap();
                 let mut iter = tokens.into_iter();
                 let Some(TokenTree::Literal(literal)) = iter.next() else {
@@ -358,9 +1974,45 @@ impl Splice<'_> {
                 literal
             }
             Kind::Char => {
-                let ch = char::from_u32(self.int as u32).unwrap();
+                let ch = char::from_u32(self.int.to_u32().unwrap()).unwrap();
                 Literal::character(ch)
             }
         }
     }
 }
+
+// A negative value is emitted as a `-` punct followed by the literal, in an
+// invisible group so that it remains one operand while still matching a
+// `$x:literal` fragment.
+fn signed(negative: bool, literal: Literal, span: Span) -> TokenTree {
+    if !negative {
+        return TokenTree::Literal(literal);
+    }
+    let mut minus = Punct::new('-', Spacing::Alone);
+    minus.set_span(span);
+    let tokens = TokenStream::from_iter([TokenTree::Punct(minus), TokenTree::Literal(literal)]);
+    let mut group = Group::new(Delimiter::None, tokens);
+    group.set_span(span);
+    TokenTree::Group(group)
+}
+
+// The compiler does not keep an invisible group together ahead of a method
+// call, field, index, or `?`, so a negative value followed by one of those, as
+// in `N.abs()`, is parenthesized instead.
+fn operand(replacement: Vec<TokenTree>, next: Option<&TokenTree>) -> Vec<TokenTree> {
+    let postfix = match next {
+        Some(TokenTree::Punct(punct)) => punct.as_char() == '.' || punct.as_char() == '?',
+        Some(TokenTree::Group(group)) => {
+            group.delimiter() == Delimiter::Parenthesis || group.delimiter() == Delimiter::Bracket
+        }
+        _ => false,
+    };
+    match replacement.as_slice() {
+        [TokenTree::Group(group)] if postfix && group.delimiter() == Delimiter::None => {
+            let mut parenthesized = Group::new(Delimiter::Parenthesis, group.stream());
+            parenthesized.set_span(group.span());
+            vec![TokenTree::Group(parenthesized)]
+        }
+        _ => replacement,
+    }
+}
@@ -1,13 +1,16 @@
-use crate::{Kind, Radix, Range, Value};
+use crate::expr::{peek_binary_op, require_closure, require_expr};
+use crate::int::{int_max, int_type, Int};
+use crate::{Adapter, Grouping, Kind, Options, Pad, Radix, Range, Source, Value};
 use proc_macro::token_stream::IntoIter as TokenIter;
 use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
 use std::borrow::Borrow;
 use std::cmp;
+use std::env;
 use std::fmt::Display;
 
 pub(crate) struct SyntaxError {
-    message: String,
-    span: Span,
+    pub(crate) message: String,
+    pub(crate) span: Span,
 }
 
 impl SyntaxError {
@@ -35,14 +38,14 @@ impl SyntaxError {
     }
 }
 
-fn next_token(iter: &mut TokenIter) -> Result<TokenTree, SyntaxError> {
+pub(crate) fn next_token(iter: &mut TokenIter) -> Result<TokenTree, SyntaxError> {
     iter.next().ok_or_else(|| SyntaxError {
         message: "unexpected end of input".to_owned(),
         span: Span::call_site(),
     })
 }
 
-fn syntax<T: Borrow<TokenTree>, M: Display>(token: T, message: M) -> SyntaxError {
+pub(crate) fn syntax<T: Borrow<TokenTree>, M: Display>(token: T, message: M) -> SyntaxError {
     SyntaxError {
         message: message.to_string(),
         span: token.borrow().span(),
@@ -75,17 +78,39 @@ pub(crate) fn require_value(iter: &mut TokenIter) -> Result<Value, SyntaxError>
                 let delimiter = group.delimiter();
                 let mut stream = group.stream().into_iter();
                 token = TokenTree::Group(group);
-                if delimiter != Delimiter::None {
+                // Parentheses are how a negative value gets substituted into
+                // a later range or a nested seq!.
+                if delimiter != Delimiter::None && delimiter != Delimiter::Parenthesis {
                     break;
                 }
                 let Some(first) = stream.next() else {
                     break;
                 };
+                if let TokenTree::Punct(punct) = &first {
+                    if punct.as_char() == '-' {
+                        let value = require_negative(&mut stream)?;
+                        require_end(&mut stream)?;
+                        return Ok(value);
+                    }
+                }
                 match stream.next() {
                     Some(_) => break,
                     None => token = first,
                 }
             }
+            TokenTree::Punct(punct) if punct.as_char() == '-' => {
+                return require_negative(iter);
+            }
+            TokenTree::Ident(ident) if ident.to_string() == "env" => {
+                return require_env(iter, &ident);
+            }
+            TokenTree::Ident(ident) => match require_if_type_const(iter, &ident)? {
+                Some(value) => return Ok(value),
+                None => {
+                    token = TokenTree::Ident(ident);
+                    break;
+                }
+            },
             TokenTree::Literal(lit) => {
                 return parse_literal(&lit).ok_or_else(|| {
                     let token = TokenTree::Literal(lit);
@@ -99,9 +124,133 @@ pub(crate) fn require_value(iter: &mut TokenIter) -> Result<Value, SyntaxError>
     Err(syntax(token, "expected integer"))
 }
 
+// `u8::MAX`, `i16::MIN`, `u32::BITS`, `char::MAX`. Returns None, consuming
+// nothing, if the ident is not followed by `::`.
+pub(crate) fn require_if_type_const(
+    iter: &mut TokenIter,
+    ty: &Ident,
+) -> Result<Option<Value>, SyntaxError> {
+    let mut lookahead = iter.clone();
+    match (lookahead.next(), lookahead.next()) {
+        (Some(TokenTree::Punct(first)), Some(TokenTree::Punct(second)))
+            if first.as_char() == ':'
+                && first.spacing() == Spacing::Joint
+                && second.as_char() == ':' => {}
+        _ => return Ok(None),
+    }
+    *iter = lookahead;
+    let name = require_ident(iter)?;
+
+    let value = |int, kind, suffix: &str| {
+        Ok(Some(Value {
+            int,
+            kind,
+            suffix: suffix.to_owned(),
+            width: 0,
+            radix: Radix::Decimal,
+            grouping: Grouping::default(),
+            span: ty.span(),
+        }))
+    };
+
+    let ty_name = ty.to_string();
+    if ty_name == "char" {
+        return match name.to_string().as_str() {
+            "MIN" => value(Int::from(0), Kind::Char, ""),
+            "MAX" => value(Int::from(char::MAX as u32), Kind::Char, ""),
+            _ => Err(syntax(TokenTree::Ident(name), "expected `MIN` or `MAX`")),
+        };
+    }
+    let Some((signed, bits)) = int_type(&ty_name) else {
+        return Err(syntax(
+            TokenTree::Ident(ty.clone()),
+            "expected primitive type",
+        ));
+    };
+
+    // The width of usize and isize depends on the target, which a procedural
+    // macro cannot know.
+    let name_str = name.to_string();
+    if bits == 0 && (signed || name_str != "MIN") {
+        return Err(SyntaxError {
+            message: format!("`{}::{}` depends on the target platform", ty_name, name),
+            span: name.span(),
+        });
+    }
+    let max = int_max(signed, bits);
+    match name_str.as_str() {
+        "MIN" if signed => value(Int::new(true, max + 1), Kind::Int, &ty_name),
+        "MIN" => value(Int::from(0), Kind::Int, &ty_name),
+        "MAX" => value(Int::new(false, max), Kind::Int, &ty_name),
+        // Left unsuffixed since the counter is not itself a u32.
+        "BITS" => value(Int::from(bits), Kind::Int, ""),
+        _ => Err(syntax(
+            TokenTree::Ident(name),
+            "expected `MIN`, `MAX`, or `BITS`",
+        )),
+    }
+}
+
+fn require_negative(iter: &mut TokenIter) -> Result<Value, SyntaxError> {
+    let mut value = require_value(iter)?;
+    if value.kind != Kind::Int || value.int.is_negative() {
+        return Err(SyntaxError {
+            message: "expected integer literal".to_owned(),
+            span: value.span,
+        });
+    }
+    value.int = Int::new(true, value.int.magnitude());
+    Ok(value)
+}
+
+// `env!("NPROC")` or `env("NPROC")`, read during expansion.
+fn require_env(iter: &mut TokenIter, env: &Ident) -> Result<Value, SyntaxError> {
+    require_if_punct(iter, '!')?;
+    let token = next_token(iter)?;
+    let mut args = match &token {
+        TokenTree::Group(group) if group.delimiter() == Delimiter::Parenthesis => {
+            group.stream().into_iter()
+        }
+        _ => return Err(syntax(token, "expected parentheses")),
+    };
+    let arg = next_token(&mut args)?;
+    let name = match &arg {
+        TokenTree::Literal(lit) => parse_string(lit),
+        _ => None,
+    };
+    let Some(name) = name else {
+        return Err(syntax(arg, "expected string literal"));
+    };
+    require_end(&mut args)?;
+
+    let span = env.span();
+    let Ok(var) = env::var(&name) else {
+        return Err(SyntaxError {
+            message: format!("environment variable `{}` not defined", name),
+            span,
+        });
+    };
+    let malformed = || SyntaxError {
+        message: format!(
+            "environment variable `{}` is not an integer literal: `{}`",
+            name, var,
+        ),
+        span,
+    };
+    let tokens = var.parse::<TokenStream>().map_err(|_| malformed())?;
+    let mut iter = tokens.into_iter();
+    if iter.clone().next().is_none() {
+        return Err(malformed());
+    }
+    let mut value = require_value(&mut iter).map_err(|_| malformed())?;
+    require_end(&mut iter).map_err(|_| malformed())?;
+    value.span = span;
+    Ok(value)
+}
+
 pub(crate) fn require_if_punct(iter: &mut TokenIter, ch: char) -> Result<bool, SyntaxError> {
     let present = match iter.clone().next() {
-        Some(TokenTree::Punct(_)) => {
+        Some(TokenTree::Punct(punct)) if punct.as_char() == ch => {
             require_punct(iter, ch)?;
             true
         }
@@ -137,93 +286,687 @@ pub(crate) fn require_end(iter: &mut TokenIter) -> Result<(), SyntaxError> {
     }
 }
 
-pub(crate) fn validate_range(
-    begin: Value,
-    end: Value,
-    inclusive: bool,
-) -> Result<Range, SyntaxError> {
-    let kind = if begin.kind == end.kind {
-        begin.kind
-    } else {
-        let expected = match begin.kind {
-            Kind::Int => "integer",
-            Kind::Byte => "byte",
-            Kind::Char => "character",
-        };
+// `N` or `(N, T)`
+pub(crate) fn require_pattern(iter: &mut TokenIter) -> Result<Vec<Ident>, SyntaxError> {
+    if let Some(TokenTree::Group(group)) = iter.clone().next() {
+        if group.delimiter() == Delimiter::Parenthesis {
+            let _ = iter.next();
+            let mut stream = group.stream().into_iter();
+            let mut vars = Vec::new();
+            loop {
+                vars.push(require_ident(&mut stream)?);
+                if !require_if_punct(&mut stream, ',')? || stream.clone().next().is_none() {
+                    break;
+                }
+            }
+            require_end(&mut stream)?;
+            return Ok(vars);
+        }
+    }
+    Ok(vec![require_ident(iter)?])
+}
+
+// The tokens of one `in` clause, up to the next `,`, the options, or the body.
+pub(crate) fn require_clause(iter: &mut TokenIter) -> Result<TokenStream, SyntaxError> {
+    let mut tokens = Vec::new();
+    while let Some(token) = iter.clone().next() {
+        match &token {
+            TokenTree::Punct(punct) if punct.as_char() == ',' => break,
+            TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => break,
+            TokenTree::Ident(ident) if ident.to_string() == "where" => break,
+            _ => {}
+        }
+        tokens.push(token);
+        let _ = iter.next();
+    }
+    if tokens.is_empty() {
+        let token = next_token(iter)?;
+        return Err(syntax(token, "expected range"));
+    }
+    Ok(TokenStream::from_iter(tokens))
+}
+
+// `where allow_empty, pad = auto, suffix = none, radix = hex`
+pub(crate) fn require_options(iter: &mut TokenIter) -> Result<Options, SyntaxError> {
+    let mut options = Options::default();
+    match iter.clone().next() {
+        Some(TokenTree::Ident(ident)) if ident.to_string() == "where" => {
+            let _ = iter.next();
+        }
+        _ => return Ok(options),
+    }
+    loop {
+        let name = require_ident(iter)?;
+        match name.to_string().as_str() {
+            "allow_empty" => options.allow_empty = true,
+            "paste_groups" => options.paste_groups = true,
+            "pad" => {
+                require_punct(iter, '=')?;
+                options.pad = Some(require_pad(iter)?);
+            }
+            "suffix" => {
+                require_punct(iter, '=')?;
+                let suffix = require_ident(iter)?;
+                let repr = suffix.to_string();
+                if repr == "none" {
+                    options.suffix = Some((String::new(), suffix.span()));
+                } else if int_type(&repr).is_some() {
+                    options.suffix = Some((repr, suffix.span()));
+                } else {
+                    let message = "expected `none` or an integer type";
+                    return Err(syntax(TokenTree::Ident(suffix), message));
+                }
+            }
+            "radix" => {
+                require_punct(iter, '=')?;
+                let radix = require_ident(iter)?;
+                options.radix = Some(match radix.to_string().as_str() {
+                    "bin" => Radix::Binary,
+                    "oct" => Radix::Octal,
+                    "dec" => Radix::Decimal,
+                    "hex" => Radix::LowerHex,
+                    "HEX" => Radix::UpperHex,
+                    _ => {
+                        let message = "expected `bin`, `oct`, `dec`, `hex`, or `HEX`";
+                        return Err(syntax(TokenTree::Ident(radix), message));
+                    }
+                });
+            }
+            _ => {
+                let message = "expected `allow_empty`, `paste_groups`, `pad`, `suffix`, or `radix`";
+                return Err(syntax(TokenTree::Ident(name), message));
+            }
+        }
+        if !require_if_punct(iter, ',')? {
+            break;
+        }
+    }
+    Ok(options)
+}
+
+fn require_pad(iter: &mut TokenIter) -> Result<Pad, SyntaxError> {
+    let token = next_token(iter)?;
+    match &token {
+        TokenTree::Ident(ident) if ident.to_string() == "auto" => Ok(Pad::Auto),
+        TokenTree::Ident(ident) if ident.to_string() == "none" => Ok(Pad::Width(0)),
+        TokenTree::Literal(_) => {
+            let mut iter = TokenStream::from(token.clone()).into_iter();
+            require_count(&mut iter, &token).map(Pad::Width)
+        }
+        _ => Err(syntax(token, "expected `auto`, `none`, or a width")),
+    }
+}
+
+pub(crate) fn require_source(iter: &mut TokenIter) -> Result<Source, SyntaxError> {
+    match iter.clone().next() {
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Bracket => {
+            let _ = iter.next();
+            return require_list(&group).map(Source::List);
+        }
+        Some(TokenTree::Ident(ident)) if ident.to_string() == "zip" => {
+            let _ = iter.next();
+            let token = next_token(iter)?;
+            let mut args = match &token {
+                TokenTree::Group(group) if group.delimiter() == Delimiter::Parenthesis => {
+                    group.stream().into_iter()
+                }
+                _ => return Err(syntax(token, "expected parentheses")),
+            };
+            let mut sources = Vec::new();
+            while args.clone().next().is_some() {
+                sources.push(require_source(&mut args)?);
+                if !require_if_punct(&mut args, ',')? {
+                    break;
+                }
+            }
+            require_end(&mut args)?;
+            if sources.is_empty() {
+                return Err(syntax(token, "expected sequences to zip"));
+            }
+            return Ok(Source::Zip(sources));
+        }
+        Some(TokenTree::Ident(ident)) if ident.to_string() == "file" => {
+            let _ = iter.next();
+            let token = next_token(iter)?;
+            let mut args = match &token {
+                TokenTree::Group(group) if group.delimiter() == Delimiter::Parenthesis => {
+                    group.stream().into_iter()
+                }
+                _ => return Err(syntax(token, "expected parentheses")),
+            };
+            let path = match next_token(&mut args)? {
+                TokenTree::Literal(lit) => lit,
+                other => return Err(syntax(other, "expected string literal")),
+            };
+            require_end(&mut args)?;
+            return Ok(Source::File(path));
+        }
+        _ => {}
+    }
+    Ok(Source::Range(require_range(iter)?))
+}
+
+// `[u8, u16, u32]` or `[1, 4, 9]`, where each element is any sequence of
+// tokens up to the next comma outside of generic arguments, as in
+// `HashMap<u8, u16>`.
+fn require_list(group: &Group) -> Result<Vec<TokenStream>, SyntaxError> {
+    let mut elements = Vec::new();
+    let mut element = Vec::<TokenTree>::new();
+    let mut depth = 0;
+    for token in group.stream() {
+        match &token {
+            // Opens generic arguments after a type name or `::`, or a qualified
+            // path like `<T as Trait>::X`, but not a shift like `1 << 4`.
+            TokenTree::Punct(punct)
+                if punct.as_char() == '<'
+                    && punct.spacing() == Spacing::Alone
+                    && match element.last() {
+                        None | Some(TokenTree::Ident(_)) => true,
+                        Some(TokenTree::Punct(prev)) => prev.as_char() == ':',
+                        Some(_) => false,
+                    } =>
+            {
+                depth += 1;
+                element.push(token);
+            }
+            // Not the `>` of `->` or `=>`.
+            TokenTree::Punct(punct)
+                if punct.as_char() == '>'
+                    && depth > 0
+                    && !matches!(element.last(), Some(TokenTree::Punct(prev))
+                        if (prev.as_char() == '-' || prev.as_char() == '=')
+                            && prev.spacing() == Spacing::Joint) =>
+            {
+                depth -= 1;
+                element.push(token);
+            }
+            TokenTree::Punct(punct) if punct.as_char() == ',' && depth == 0 => {
+                if element.is_empty() {
+                    return Err(syntax(token, "expected element"));
+                }
+                elements.push(TokenStream::from_iter(element.drain(..)));
+            }
+            _ => element.push(token),
+        }
+    }
+    if !element.is_empty() {
+        elements.push(TokenStream::from_iter(element));
+    }
+    Ok(elements)
+}
+
+// `{ name: ctrl, addr: 0x00 }`
+pub(crate) fn require_fields(group: &Group) -> Result<Vec<(Ident, TokenStream)>, SyntaxError> {
+    let mut fields = Vec::<(Ident, TokenStream)>::new();
+    for field in require_list(group)? {
+        let mut iter = field.into_iter();
+        let name = require_ident(&mut iter)?;
+        require_punct(&mut iter, ':')?;
+        if iter.clone().next().is_none() {
+            return Err(SyntaxError {
+                message: format!("expected value of field `{}`", name),
+                span: name.span(),
+            });
+        }
+        if fields
+            .iter()
+            .any(|(prev, _)| prev.to_string() == name.to_string())
+        {
+            return Err(SyntaxError {
+                message: format!("duplicate field `{}`", name),
+                span: name.span(),
+            });
+        }
+        fields.push((name, TokenStream::from_iter(iter)));
+    }
+    Ok(fields)
+}
+
+pub(crate) fn require_range(iter: &mut TokenIter) -> Result<Range, SyntaxError> {
+    if let Some(TokenTree::Group(group)) = iter.clone().next() {
+        if group.delimiter() == Delimiter::Parenthesis && contains_range(&group) {
+            let _ = iter.next();
+            let mut stream = group.stream().into_iter();
+            let mut range = require_range(&mut stream)?;
+            require_end(&mut stream)?;
+            while require_if_punct(iter, '.')? {
+                let adapter = require_adapter(iter, &range)?;
+                if let Adapter::Chain(other) = &adapter {
+                    range.suffix = validate_suffix(&range.suffix, &other.suffix, other.span)?;
+                    validate_chain(&range, &range.suffix)?;
+                    validate_chain(other, &range.suffix)?;
+                    validate_sign(&range.suffix, [range.begin, other.begin], other.span)?;
+                    range.radix = validate_radix(range.radix, other.radix, other.span)?;
+                    range.width = cmp::min(range.width, other.width);
+                    if range.grouping.sizes.is_empty() {
+                        range.grouping = other.grouping.clone();
+                    }
+                }
+                range.adapters.push(adapter);
+            }
+            return Ok(range);
+        }
+    }
+
+    let begin = require_bound(iter)?;
+    require_punct(iter, '.')?;
+    require_punct(iter, '.')?;
+    let inclusive = require_if_punct(iter, '=')?;
+    let open_ended = match iter.clone().next() {
+        None => true,
+        Some(TokenTree::Punct(punct)) => punct.as_char() == ',',
+        Some(_) => false,
+    };
+    if open_ended && !inclusive {
+        let end = type_max(&begin)?;
+        return validate_range(begin, end, true);
+    }
+    let end = require_bound(iter)?;
+    validate_range(begin, end, inclusive)
+}
+
+// The end of `0u8..` or `b'\0'..`, which is the maximum value of the type.
+fn type_max(begin: &Value) -> Result<Value, SyntaxError> {
+    let int = match begin.kind {
+        Kind::Int => match int_type(&begin.suffix) {
+            Some((signed, bits)) if bits > 0 => Int::new(false, int_max(signed, bits)),
+            Some(_) => {
+                return Err(SyntaxError {
+                    message: format!(
+                        "the end of an open-ended `{}` range depends on the target platform",
+                        begin.suffix,
+                    ),
+                    span: begin.span,
+                });
+            }
+            None => {
+                return Err(SyntaxError {
+                    message: "open-ended range requires a suffixed start, as in `0u8..`".to_owned(),
+                    span: begin.span,
+                });
+            }
+        },
+        Kind::Byte => Int::from(0xFF),
+        Kind::Char => Int::from(char::MAX as u32),
+    };
+    Ok(Value {
+        int,
+        kind: begin.kind,
+        suffix: begin.suffix.clone(),
+        width: 0,
+        radix: Radix::Decimal,
+        grouping: Grouping::default(),
+        span: begin.span,
+    })
+}
+
+// Distinguishes `(0..8).rev()` from a parenthesized bound `(1 << 2)..8`.
+fn contains_range(group: &Group) -> bool {
+    let tokens = Vec::from_iter(group.stream());
+    tokens.windows(2).any(|pair| match pair {
+        [TokenTree::Punct(first), TokenTree::Punct(second)] => {
+            first.as_char() == '.' && first.spacing() == Spacing::Joint && second.as_char() == '.'
+        }
+        _ => false,
+    })
+}
+
+// A literal bound, or a constant expression like `1 << 4` or `2 * 8 + 1`. The
+// formatting of an expression comes from its leftmost operand, and a suffix
+// from any operand.
+fn require_bound(iter: &mut TokenIter) -> Result<Value, SyntaxError> {
+    let begin = iter.clone();
+    let is_group = matches!(
+        iter.clone().next(),
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis
+            || group.delimiter() == Delimiter::None
+    );
+    let mut literal_error = None;
+    if !is_group {
+        match require_value(iter) {
+            Ok(value) if peek_binary_op(iter).is_none() => return Ok(value),
+            Ok(_) => {}
+            Err(error) => literal_error = Some(error),
+        }
+        *iter = begin.clone();
+    }
+
+    // If the bound is neither a literal nor an expression, report why it is
+    // not a literal.
+    let expr = require_expr(iter).map_err(|error| literal_error.take().unwrap_or(error))?;
+    let len = begin.clone().count() - iter.clone().count();
+    let tokens = Vec::from_iter(begin.take(len));
+    let value = expr
+        .eval(&|_| None)
+        .map_err(|error| literal_error.take().unwrap_or(error))?;
+    let int = value.to_int(expr.span())?;
+
+    let mut operands = Vec::new();
+    collect_operands(
+        TokenStream::from_iter(tokens.clone()).into_iter(),
+        &mut operands,
+    );
+    let Some(first) = operands.first() else {
+        return Err(syntax(&tokens[0], "expected integer"));
+    };
+    let mut suffix = first.suffix.clone();
+    for operand in &operands[1..] {
+        if operand.kind != first.kind {
+            return Err(SyntaxError {
+                message: format!("expected {} literal", first.kind.name()),
+                span: operand.span,
+            });
+        }
+        suffix = validate_suffix(&suffix, &operand.suffix, operand.span)?;
+    }
+    let span = tokens[0].span();
+    if !first.kind.contains(int) {
         return Err(SyntaxError {
-            message: format!("expected {} literal", expected),
-            span: end.span,
+            message: format!("value out of range for {} range", first.kind.name()),
+            span,
         });
+    }
+    Ok(Value {
+        int,
+        kind: first.kind,
+        suffix,
+        width: first.width,
+        radix: first.radix,
+        grouping: first.grouping.clone(),
+        span,
+    })
+}
+
+fn collect_operands(mut iter: TokenIter, operands: &mut Vec<Value>) {
+    while let Some(token) = iter.next() {
+        match token {
+            TokenTree::Literal(lit) => operands.extend(parse_literal(&lit)),
+            TokenTree::Ident(ident) => {
+                if let Ok(Some(value)) = require_if_type_const(&mut iter, &ident) {
+                    operands.push(value);
+                }
+            }
+            TokenTree::Group(group) => collect_operands(group.stream().into_iter(), operands),
+            TokenTree::Punct(_) => {}
+        }
+    }
+}
+
+fn require_adapter(iter: &mut TokenIter, range: &Range) -> Result<Adapter, SyntaxError> {
+    let method = TokenTree::Ident(require_ident(iter)?);
+    let token = next_token(iter)?;
+    let mut args = match &token {
+        TokenTree::Group(group) if group.delimiter() == Delimiter::Parenthesis => {
+            group.stream().into_iter()
+        }
+        _ => return Err(syntax(token, "expected parentheses")),
+    };
+    let adapter = match method.to_string().as_str() {
+        "rev" => Adapter::Rev,
+        "step_by" => {
+            let step = require_count(&mut args, &token)?;
+            if step == 0 {
+                return Err(syntax(token, "step size must not be zero"));
+            }
+            Adapter::StepBy(step)
+        }
+        "skip" => Adapter::Skip(require_count(&mut args, &token)?),
+        "take" => Adapter::Take(require_count(&mut args, &token)?),
+        "map" => Adapter::Map(require_closure(&mut args)?),
+        "filter" => Adapter::Filter(require_closure(&mut args)?),
+        "chain" => {
+            let other = require_range(&mut args)?;
+            if other.kind != range.kind {
+                return Err(SyntaxError {
+                    message: format!("expected {} range", range.kind.name()),
+                    span: other.span,
+                });
+            }
+            Adapter::Chain(Box::new(other))
+        }
+        _ => {
+            return Err(syntax(
+                method,
+                "expected `rev`, `step_by`, `skip`, `take`, `chain`, `map`, or `filter`",
+            ));
+        }
     };
+    require_end(&mut args)?;
+    Ok(adapter)
+}
 
-    let suffix = if begin.suffix.is_empty() {
-        end.suffix
-    } else if end.suffix.is_empty() || begin.suffix == end.suffix {
-        begin.suffix
-    } else {
+fn require_count(iter: &mut TokenIter, parens: &TokenTree) -> Result<usize, SyntaxError> {
+    if iter.clone().next().is_none() {
+        return Err(syntax(parens, "expected integer"));
+    }
+    let value = require_value(iter)?;
+    let count = match value.kind {
+        Kind::Int if !value.int.is_negative() && matches!(&*value.suffix, "" | "usize") => {
+            usize::try_from(value.int.magnitude()).ok()
+        }
+        _ => None,
+    };
+    count.ok_or_else(|| SyntaxError {
+        message: "expected usize literal".to_owned(),
+        span: value.span,
+    })
+}
+
+fn validate_range(begin: Value, end: Value, inclusive: bool) -> Result<Range, SyntaxError> {
+    if begin.kind != end.kind {
         return Err(SyntaxError {
-            message: format!("expected suffix `{}`", begin.suffix),
+            message: format!("expected {} literal", begin.kind.name()),
             span: end.span,
         });
+    }
+
+    // `10..3` is almost certainly a mistake, unlike `5..5` or `1..=0`. Whether
+    // it is an error is decided by `Source::check_reversed`, because bounds
+    // computed from other variables may legitimately cross.
+    let reversed = if inclusive {
+        end.int.successor().map_or(false, |next| begin.int > next)
+    } else {
+        begin.int > end.int
     };
 
-    let radix = if begin.radix == end.radix {
-        begin.radix
-    } else if begin.radix == Radix::LowerHex && end.radix == Radix::UpperHex
-        || begin.radix == Radix::UpperHex && end.radix == Radix::LowerHex
-    {
-        Radix::UpperHex
+    let suffix = validate_suffix(&begin.suffix, &end.suffix, end.span)?;
+    validate_sign(&suffix, [begin.int, end.int], begin.span)?;
+
+    // Every value generated must fit in the suffix type, for example `0u8..256`
+    // is fine but `250u8..=300` is not.
+    let last = if inclusive {
+        Some(end.int)
     } else {
-        let expected = match begin.radix {
-            Radix::Binary => "binary",
-            Radix::Octal => "octal",
-            Radix::Decimal => "base 10",
-            Radix::LowerHex | Radix::UpperHex => "hexadecimal",
-        };
-        return Err(SyntaxError {
-            message: format!("expected {} literal", expected),
-            span: end.span,
-        });
+        end.int.checked_sub(Int::from(1))
+    };
+    if let Some(last) = last {
+        if begin.kind == Kind::Int && begin.int <= last {
+            begin.kind.check(begin.int, &suffix, begin.span)?;
+            begin.kind.check(last, &suffix, end.span)?;
+        }
+    }
+
+    // A bound without digits of its own, like `u8::MAX`, takes on the
+    // formatting of the other bound.
+    let (radix, width) = if end.width == 0 {
+        (begin.radix, begin.width)
+    } else if begin.width == 0 {
+        (end.radix, end.width)
+    } else {
+        let radix = validate_radix(begin.radix, end.radix, end.span)?;
+        (radix, cmp::min(begin.width, end.width))
+    };
+    let grouping = if begin.grouping.sizes.is_empty() {
+        end.grouping
+    } else {
+        begin.grouping
     };
 
     Ok(Range {
         begin: begin.int,
         end: end.int,
         inclusive,
-        kind,
+        adapters: Vec::new(),
+        kind: begin.kind,
         suffix,
-        width: cmp::min(begin.width, end.width),
+        width,
         radix,
+        grouping,
+        reversed: reversed.then_some(end.span),
+        span: begin.span,
     })
 }
 
-fn parse_literal(lit: &Literal) -> Option<Value> {
-    let span = lit.span();
-    let repr = lit.to_string();
-    assert!(!repr.starts_with('_'));
+// Every value of a chain must fit in the suffix type merged from all of its
+// ranges, as in `(250..300).chain(0u8..1)` which is an error.
+fn validate_chain(range: &Range, suffix: &str) -> Result<(), SyntaxError> {
+    let last = if range.inclusive {
+        Some(range.end)
+    } else {
+        range.end.checked_sub(Int::from(1))
+    };
+    if let Some(last) = last {
+        if range.kind == Kind::Int && range.begin <= last {
+            range.kind.check(range.begin, suffix, range.span)?;
+            range.kind.check(last, suffix, range.span)?;
+        }
+    }
+    for adapter in &range.adapters {
+        if let Adapter::Chain(other) = adapter {
+            validate_chain(other, suffix)?;
+        }
+    }
+    Ok(())
+}
 
-    if repr.starts_with("b'") && repr.ends_with('\'') && repr.len() == 4 {
-        return Some(Value {
-            int: repr.as_bytes()[2] as u64,
-            kind: Kind::Byte,
-            suffix: String::new(),
-            width: 0,
-            radix: Radix::Decimal,
+fn validate_suffix(first: &str, second: &str, span: Span) -> Result<String, SyntaxError> {
+    if first.is_empty() {
+        Ok(second.to_owned())
+    } else if second.is_empty() || first == second {
+        Ok(first.to_owned())
+    } else {
+        Err(SyntaxError {
+            message: format!("expected suffix `{}`", first),
             span,
-        });
+        })
     }
+}
 
-    if repr.starts_with('\'') && repr.ends_with('\'') && repr.chars().count() == 3 {
-        return Some(Value {
-            int: repr[1..].chars().next().unwrap() as u64,
-            kind: Kind::Char,
-            suffix: String::new(),
-            width: 0,
-            radix: Radix::Decimal,
+fn validate_sign(suffix: &str, ints: [Int; 2], span: Span) -> Result<(), SyntaxError> {
+    if suffix.starts_with('u') && ints.iter().any(|int| int.is_negative()) {
+        return Err(SyntaxError {
+            message: format!("negative value with unsigned suffix `{}`", suffix),
             span,
         });
     }
+    Ok(())
+}
+
+fn validate_radix(first: Radix, second: Radix, span: Span) -> Result<Radix, SyntaxError> {
+    if first == second {
+        Ok(first)
+    } else if first == Radix::LowerHex && second == Radix::UpperHex
+        || first == Radix::UpperHex && second == Radix::LowerHex
+    {
+        Ok(Radix::UpperHex)
+    } else {
+        let expected = match first {
+            Radix::Binary => "binary",
+            Radix::Octal => "octal",
+            Radix::Decimal => "base 10",
+            Radix::LowerHex | Radix::UpperHex => "hexadecimal",
+        };
+        Err(SyntaxError {
+            message: format!("expected {} literal", expected),
+            span,
+        })
+    }
+}
+
+// Contents of a string literal without escapes, such as a path or the name of
+// an environment variable.
+pub(crate) fn parse_string(lit: &Literal) -> Option<String> {
+    let repr = lit.to_string();
+    let content = repr.strip_prefix('"')?.strip_suffix('"')?;
+    if content.contains('\\') {
+        return None;
+    }
+    Some(content.to_owned())
+}
+
+pub(crate) fn parse_literal(lit: &Literal) -> Option<Value> {
+    let span = lit.span();
+    let repr = lit.to_string();
+    assert!(!repr.starts_with('_'));
+
+    // A single literal token can be negative, for example a `$n:literal`
+    // forwarded by macro_rules.
+    if let Some(magnitude) = repr.strip_prefix('-') {
+        return parse_integer(magnitude, true, span);
+    }
 
+    let (kind, content) = if let Some(content) = repr.strip_prefix("b'") {
+        (Kind::Byte, content.strip_suffix('\'')?)
+    } else if let Some(content) = repr.strip_prefix('\'') {
+        (Kind::Char, content.strip_suffix('\'')?)
+    } else {
+        return parse_integer(&repr, false, span);
+    };
+    Some(Value {
+        int: Int::from(parse_char(content, kind == Kind::Byte)?),
+        kind,
+        suffix: String::new(),
+        width: 0,
+        radix: Radix::Decimal,
+        grouping: Grouping::default(),
+        span,
+    })
+}
+
+// The code point of the content of a char or byte literal, decoding escapes
+// such as `\n`, `\x7f`, and `\u{1F600}`.
+fn parse_char(content: &str, byte: bool) -> Option<u32> {
+    let mut chars = content.chars();
+    let ch = chars.next()?;
+    if ch != '\\' {
+        return match chars.next() {
+            None if !byte || ch.is_ascii() => Some(ch as u32),
+            _ => None,
+        };
+    }
+    let code = match chars.next()? {
+        'n' => '\n' as u32,
+        'r' => '\r' as u32,
+        't' => '\t' as u32,
+        '\\' => '\\' as u32,
+        '0' => 0,
+        '\'' => '\'' as u32,
+        '"' => '"' as u32,
+        'x' => {
+            let code = u32::from_str_radix(chars.as_str(), 16).ok()?;
+            if chars.as_str().len() != 2 || !byte && code > 0x7F {
+                return None;
+            }
+            return Some(code);
+        }
+        'u' if !byte => {
+            let digits = chars.as_str().strip_prefix('{')?.strip_suffix('}')?;
+            let digits = digits.replace('_', "");
+            if digits.is_empty() || digits.len() > 6 {
+                return None;
+            }
+            let code = u32::from_str_radix(&digits, 16).ok()?;
+            return char::from_u32(code).map(|ch| ch as u32);
+        }
+        _ => return None,
+    };
+    match chars.next() {
+        None => Some(code),
+        Some(_) => None,
+    }
+}
+
+fn parse_integer(repr: &str, negative: bool, span: Span) -> Option<Value> {
     let (mut radix, radix_n) = if repr.starts_with("0b") {
         (Radix::Binary, 2)
     } else if repr.starts_with("0o") {
@@ -239,6 +982,8 @@ fn parse_literal(lit: &Literal) -> Option<Value> {
     let mut iter = repr.char_indices();
     let mut digits = String::new();
     let mut suffix = String::new();
+    // Number of digits between underscores.
+    let mut groups = vec![0];
 
     if radix != Radix::Decimal {
         let _ = iter.nth(1);
@@ -246,7 +991,7 @@ fn parse_literal(lit: &Literal) -> Option<Value> {
 
     for (i, ch) in iter {
         match ch {
-            '_' => {}
+            '_' => groups.push(0),
             '0'..='9' => digits.push(ch),
             'A'..='F' if radix == Radix::LowerHex => {
                 digits.push(ch);
@@ -258,22 +1003,26 @@ fn parse_literal(lit: &Literal) -> Option<Value> {
                 if digits.is_empty() {
                     return None;
                 }
-                suffix = repr;
-                suffix.replace_range(..i, "");
+                suffix = repr[i..].to_owned();
                 break;
             }
         }
+        if ch != '_' {
+            *groups.last_mut().unwrap() += 1;
+        }
     }
 
-    let int = u64::from_str_radix(&digits, radix_n).ok()?;
+    let int = Int::new(negative, u128::from_str_radix(&digits, radix_n).ok()?);
     let kind = Kind::Int;
     let width = digits.len();
+    let grouping = Grouping::from_groups(groups);
     Some(Value {
         int,
         kind,
         suffix,
         width,
         radix,
+        grouping,
         span,
     })
 }
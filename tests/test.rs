@@ -1,6 +1,7 @@
 #![allow(
     clippy::derive_partial_eq_without_eq,
     clippy::identity_op,
+    clippy::init_numbered_fields,
     clippy::let_underscore_untyped,
     clippy::shadow_unrelated
 )]
@@ -285,4 +286,750 @@ fn test_nested() {
     });
 
     assert_eq!(vec, ["T1", "T1 T2", "T1 T2 T3"]);
+
+    let strings = seq!(I in 1..=2 {
+        [#(seq!(J in 0..I { stringify!(#(#{J * 2} #{J * 10 + I})*) }),)*]
+    });
+    assert_eq!(strings, ["0 1", "0 2 2 12"]);
+
+    let strings = seq!(I in 1..=3 {
+        [#(seq!(J in 0..I { stringify!(#len #(#index)*) }),)*]
+    });
+    assert_eq!(strings, ["1 0", "2 0 1", "3 0 1 2"]);
+
+    let strings = seq!(I in 1..=2 {
+        [#(seq!(J in 0..=I {
+            stringify!(#(#if J == 0 { zero } #else #if J == I { last } #else { J })*)
+        }),)*]
+    });
+    assert_eq!(strings, ["zero last", "zero 1 last"]);
+}
+
+#[test]
+fn test_negative() {
+    let values = seq!(N in -3..3 { [#(N,)*] });
+    assert_eq!(values, [-3, -2, -1, 0, 1, 2]);
+
+    let s = seq!(N in -2i8..=-1i8 { stringify!(#(N)*) });
+    assert_eq!(s, "- 2i8 - 1i8");
+
+    let n = seq!(N in -128i8..=127i8 { 0 #(+ 1)* });
+    assert_eq!(n, 256);
+
+    seq!(N in -0x2..0x1 {
+        #[derive(Debug)]
+        enum Offset {
+            #(
+                Off~N,
+            )*
+        }
+    });
+    let offsets = [Offset::OffNeg2, Offset::OffNeg1, Offset::Off0];
+    assert_eq!(format!("{:?}", offsets), "[OffNeg2, OffNeg1, Off0]");
+
+    macro_rules! forward {
+        ($begin:literal, $end:expr) => {
+            seq!(N in $begin..$end { [#(N,)*] })
+        };
+    }
+    assert_eq!(forward!(-2, -1), [-2]);
+
+    let abs = seq!(N in -3i32..-2i32 { N.abs() });
+    assert_eq!(abs, 3);
+
+    let pow = seq!(N in -2..-1 { N * N });
+    assert_eq!(pow, 4);
+
+    macro_rules! literal {
+        ($x:literal) => {
+            $x
+        };
+    }
+    let values = seq!(N in -2..0 { [#(literal!(N),)*] });
+    assert_eq!(values, [-2, -1]);
+    let abs = seq!(N in -3i32..-2i32 { #{N}.abs() });
+    assert_eq!(abs, 3);
+}
+
+#[test]
+fn test_128bit() {
+    let values = seq!(N in 0xffff_ffff_ffff_ffff_ffff_ffff_ffff_fffdu128..=0xffff_ffff_ffff_ffff_ffff_ffff_ffff_ffffu128 { [#(N,)*] });
+    assert_eq!(values, [u128::MAX - 2, u128::MAX - 1, u128::MAX]);
+
+    let values = seq!(N in -170141183460469231731687303715884105728i128..-170141183460469231731687303715884105726i128 { [#(N,)*] });
+    assert_eq!(values, [i128::MIN, i128::MIN + 1]);
+
+    seq!(N in 0x1_0000_0000_0000_0000..0x1_0000_0000_0000_0002 {
+        #(
+            struct Reg~N;
+        )*
+    });
+    let _ = (Reg10000000000000000, Reg10000000000000001);
+
+    let s = seq!(X in 0x1_0000_0000_0000_0000_0000i128..0x1_0000_0000_0000_0000_0002i128 { stringify!(#(X)*) });
+    let expected = "0x1_0000_0000_0000_0000_0000i128 0x1_0000_0000_0000_0000_0001i128";
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn test_adapters() {
+    let values = seq!(N in (0..64).step_by(8).rev() { [#(N,)*] });
+    assert_eq!(values, [56, 48, 40, 32, 24, 16, 8, 0]);
+
+    let values = seq!(N in (0..4).chain(8..12) { [#(N,)*] });
+    assert_eq!(values, [0, 1, 2, 3, 8, 9, 10, 11]);
+
+    let values = seq!(N in (0..18446744073709551615).skip(2).take(3) { [#(N,)*] });
+    assert_eq!(values, [2, 3, 4]);
+
+    let s = seq!(X in (0x0a..0x0c).chain((0x0E..=0x10).rev()) { stringify!(#(X)*) });
+    assert_eq!(s, "0x0A 0x0B 0x10 0x0F 0x0E");
+
+    let s = seq!(ch in ('a'..='c').chain('x'..='z').step_by(2) { stringify!(#(ch)*) });
+    assert_eq!(s, "'a' 'c' 'y'");
+
+    let values = seq!(N in (0u32..).rev().take(2) { [#(N,)*] });
+    assert_eq!(values, [u32::MAX, u32::MAX - 1]);
+
+    let values = seq!(N in (-2..=1).rev().rev().rev() { [#(N,)*] });
+    assert_eq!(values, [1, 0, -1, -2]);
+
+    let s = seq!(ch in ('a'..'d').rev() { stringify!(#(ch)*) });
+    assert_eq!(s, "'c' 'b' 'a'");
+
+    let values = seq!(N in (250..256).chain(0u8..1) { [#(N,)*] });
+    assert_eq!(values, [250u8, 251, 252, 253, 254, 255, 0]);
+}
+
+#[test]
+fn test_map_filter() {
+    let values = seq!(N in (0..32).filter(|n| n % 2 == 0).map(|n| n * 4) { [#(N,)*] });
+    assert_eq!(
+        values,
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 72, 80, 88, 96, 104, 112, 120]
+    );
+
+    let values = seq!(N in (0..10).filter(|n| n % 3 != 0 && !(n > 7)) { [#(N,)*] });
+    assert_eq!(values, [1, 2, 4, 5, 7]);
+
+    let values = seq!(N in (-2..3).map(|n| n * n - 1 << 1 | 1) { [#(N,)*] });
+    assert_eq!(values, [7, 1, -1, 1, 7]);
+
+    let s = seq!(X in (0x0..0x4).map(|x| x * 4) { stringify!(#(X)*) });
+    assert_eq!(s, "0x0 0x4 0x8 0xc");
+
+    let bytes = seq!(c in (b'a'..=b'c').map(|c| c - (b'a' - b'A')) { [#(c,)*] });
+    assert_eq!(bytes, *b"ABC");
+}
+
+#[test]
+fn test_interpolation() {
+    let offsets = seq!(N in 0..4 { [#(#{N * 8 + 4},)*] });
+    assert_eq!(offsets, [4, 12, 20, 28]);
+
+    let masks = seq!(N in 0x0..0x3 { stringify!(#(#{1 << N})*) });
+    assert_eq!(masks, "0x1 0x2 0x4");
+
+    let flags = seq!(N in 0..3 { [#(#{N % 2 == 0},)*] });
+    assert_eq!(flags, [true, false, true]);
+
+    seq!(N in 1..4 {
+        fn stage~N() -> u64 {
+            stage~{N - 1}() + N
+        }
+    });
+    fn stage0() -> u64 {
+        0
+    }
+    assert_eq!(stage3(), 6);
+}
+
+#[test]
+fn test_paste_chain() {
+    seq!(N in 0..2 {
+        #(
+            fn reg~N~_ctrl() -> u8 { N }
+            #[derive(Debug)]
+            struct Lane~N~Mask;
+            fn _~N() -> u8 { N }
+            fn a~N~b~N~c() -> u8 { N }
+            fn x~1~N() -> u8 { N }
+        )*
+    });
+    assert_eq!((reg0_ctrl(), reg1_ctrl()), (0, 1));
+    assert_eq!(
+        format!("{:?} {:?}", Lane0Mask, Lane1Mask),
+        "Lane0Mask Lane1Mask"
+    );
+    assert_eq!((_0(), _1()), (0, 1));
+    assert_eq!((a0b0c(), a1b1c()), (0, 1));
+    assert_eq!((x10(), x11()), (0, 1));
+}
+
+#[test]
+fn test_separator() {
+    let s = seq!(N in 0..3 { stringify!(#(T~N),*) });
+    assert_eq!(s, "T0, T1, T2");
+
+    let sum = seq!(N in 1..=4 { #(N)+* });
+    assert_eq!(sum, 10);
+
+    let even = |n: u8| seq!(N in (0..8).step_by(2) { matches!(n, #(N)|*) });
+    assert!(even(6));
+    assert!(!even(3));
+
+    let empty: [u8; 0] = seq!(N in 0..0 where allow_empty { [#(N),*] });
+    assert_eq!(empty, []);
+
+    let x = [true, true, false];
+    let all = seq!(N in 0..2 { #(x[N])&&* });
+    assert!(all);
+    let any = seq!(N in 1..3 { #(x[N])||* });
+    assert!(any);
+
+    mod m0 {
+        pub mod m1 {
+            pub const V: u8 = 7;
+        }
+    }
+    let v = seq!(N in 0..2 { #(m~N)::*::V });
+    assert_eq!(v, 7);
+    let s = seq!(N in 0..2 { stringify!(#(N)=>*) });
+    assert_eq!(s, "0 => 1");
+}
+
+#[test]
+fn test_metavariables() {
+    let slots = seq!(N in (10..40).step_by(10) { [#((#index, N, #len)),*] });
+    assert_eq!(slots, [(0, 10, 3), (1, 20, 3), (2, 30, 3)]);
+
+    let edges = seq!(N in 0..3 { [#((#first, #last)),*] });
+    assert_eq!(edges, [(true, false), (false, false), (false, true)]);
+
+    let mut total = 0;
+    seq!(N in 5..7 {
+        total += (#index + 1) * #{#len - #index} * N;
+    });
+    assert_eq!(total, 1 * 2 * 5 + 2 * 1 * 6);
+
+    seq!(N in 'a'..='z' {
+        const LETTERS: [char; #len] = [#(N),*];
+    });
+    assert_eq!(LETTERS.len(), 26);
+}
+
+#[test]
+fn test_conditional() {
+    let values = seq!(N in 0..6 {
+        [#(
+            #if N % 2 == 0 { #{N * 10}, } #else { N, }
+        )*]
+    });
+    assert_eq!(values, [0, 1, 20, 3, 40, 5]);
+
+    let kinds = seq!(N in 0..4 {
+        [#(
+            #if #first {
+                "first"
+            } #else #if #last {
+                "last"
+            } #else #if N == 2 && #len > 3 {
+                "two"
+            } #else {
+                "middle"
+            },
+        )*]
+    });
+    assert_eq!(kinds, ["first", "middle", "two", "last"]);
+
+    seq!(N in 0..3 {
+        #if N > 0 {
+            fn stage~N() -> u64 {
+                stage~{N - 1}() + 1
+            }
+        } #else {
+            fn stage~N() -> u64 {
+                100
+            }
+        }
+    });
+    assert_eq!(stage2(), 102);
+
+    let skipped = seq!(N in 0..4 { [#(#if N != 1 { N, })*] });
+    assert_eq!(skipped, [0, 2, 3]);
+}
+
+#[test]
+fn test_multiple_vars() {
+    let cells = seq!(R in 0..3, C in 0..R { [#((R, C),)*] });
+    assert_eq!(cells, [(1, 0), (2, 0), (2, 1)]);
+
+    seq!(R in 1..=2, C in 0..2 {
+        const CELL~R~C: usize = R * 10 + C;
+    });
+    assert_eq!([CELL10, CELL11, CELL20, CELL21], [10, 11, 20, 21]);
+
+    let grid = seq!(R in 0..2, C in 0..3 { [#(#{R * 3 + C} #if C == 2 { + #len },)*] });
+    assert_eq!(grid, [0, 1, 8, 3, 4, 11]);
+
+    let cells = seq!(R in 0..4, C in (R + 2)..4 { [#((R, C),)*] });
+    assert_eq!(cells, [(0, 2), (0, 3), (1, 3)]);
+}
+
+#[test]
+fn test_zip() {
+    trait Discriminant {
+        const DISCRIMINANT: u8;
+    }
+
+    seq!((N, T) in zip(0..3, [u8, u16, u32]) {
+        impl Discriminant for T {
+            const DISCRIMINANT: u8 = N;
+        }
+    });
+    assert_eq!(u8::DISCRIMINANT, 0);
+    assert_eq!(u16::DISCRIMINANT, 1);
+    assert_eq!(u32::DISCRIMINANT, 2);
+
+    let pairs = seq!((A, B) in zip(0..3, (0..6).step_by(2).rev()) { [#((A, B),)*] });
+    assert_eq!(pairs, [(0, 4), (1, 2), (2, 0)]);
+
+    let names = seq!((N, S, T) in zip(1..=2, ["one", "two"], [Vec<u8>, Option<u8>]) {
+        [#((N, S, core::mem::size_of::<T>()),)*]
+    });
+    assert_eq!(names[0], (1, "one", core::mem::size_of::<Vec<u8>>()));
+    assert_eq!(names[1], (2, "two", 2));
+}
+
+#[test]
+fn test_value_list() {
+    struct Foo<T>(T);
+    struct Bar;
+
+    seq!(T in [u8, i16, Foo<Bar>] {
+        let _: Option<T> = None;
+    });
+
+    let sizes = seq!(T in [u8, i16, u64] { [#(core::mem::size_of::<T>(),)*] });
+    assert_eq!(sizes, [1, 2, 8]);
+
+    let strings = seq!(S in ["name", "addr"] { [#(S,)*] });
+    assert_eq!(strings, ["name", "addr"]);
+
+    seq!(T in [u8, u16] {
+        fn max_~T() -> T {
+            T::MAX
+        }
+    });
+    assert_eq!(max_u8(), u8::MAX);
+    assert_eq!(max_u16(), u16::MAX);
+
+    seq!(N in [1, 4, 0x10] {
+        const SQUARE~N: u32 = #{N * N};
+    });
+    assert_eq!([SQUARE1, SQUARE4, SQUARE10], [1, 16, 256]);
+}
+
+#[test]
+fn test_list_generics() {
+    use std::collections::HashMap;
+
+    let names = seq!(T in [u8, HashMap<u8, u16>, Vec<Result<(), (u8, u8)>>] {
+        [#(core::any::type_name::<T>(),)*]
+    });
+    assert_eq!(names.len(), 3);
+    assert!(names[1].ends_with("HashMap<u8, u16>"));
+
+    let values = seq!(N in [1 << 4, 2 >> 1, 3] { [#(N,)*] });
+    assert_eq!(values, [16, 1, 3]);
+}
+
+#[test]
+fn test_records() {
+    struct Regs {
+        values: [u8; 8],
+    }
+
+    seq!(R in [{ name: ctrl, addr: 0x00 }, { name: status, addr: 0x04 }] {
+        impl Regs {
+            #(
+                fn get_~R.name(&self) -> u8 {
+                    self.values[R.addr]
+                }
+            )*
+        }
+
+        const OFFSETS: [(&str, usize); 2] = [#((stringify!(R.name), #{R.addr + 1}),)*];
+    });
+
+    let regs = Regs {
+        values: [1, 2, 3, 4, 5, 6, 7, 8],
+    };
+    assert_eq!(regs.get_ctrl(), 1);
+    assert_eq!(regs.get_status(), 5);
+    assert_eq!(OFFSETS, [("ctrl", 0x01), ("status", 0x05)]);
+}
+
+#[test]
+fn test_file() {
+    seq!(R in file("tests/regs.csv") {
+        fn reg_~R.name() -> (usize, usize) {
+            (R.addr, core::mem::size_of::<R.ty>())
+        }
+    });
+    assert_eq!(reg_ctrl(), (0x00, 1));
+    assert_eq!(reg_status(), (0x04, 2));
+    assert_eq!(reg_data(), (0x08, 8));
+
+    struct Regs;
+    impl Regs {
+        seq!(R in file("tests/regs.csv") {
+            fn addr_~R.name() -> usize { R.addr }
+        });
+    }
+    let mut total = 0;
+    seq!(R in file("tests/regs.csv") {
+        total += R.addr;
+        let addr_~R.name = R.addr;
+    });
+    assert_eq!(total, addr_ctrl + addr_status + addr_data);
+    assert_eq!(
+        (Regs::addr_ctrl(), Regs::addr_status(), Regs::addr_data()),
+        (0x00, 0x04, 0x08),
+    );
+}
+
+#[test]
+fn test_env() {
+    let minor: usize = env!("CARGO_PKG_VERSION_MINOR").parse().unwrap();
+    let values = seq!(N in 0..=env!("CARGO_PKG_VERSION_MINOR") { [#(N,)*] });
+    assert_eq!(values.len(), minor + 1);
+    let values = seq!(N in env("CARGO_PKG_VERSION_MAJOR")..2 { [#(N,)*] });
+    assert_eq!(values, [0, 1]);
+
+    struct Counter;
+    impl Counter {
+        seq!(N in 0..=env!("CARGO_PKG_VERSION_MAJOR") {
+            fn count~N() -> usize { N }
+        });
+    }
+    let mut total = 0;
+    seq!(N in 0..=env!("CARGO_PKG_VERSION_MAJOR") {
+        total += 1;
+        let v~N = N;
+    });
+    assert_eq!((total, v0, Counter::count0()), (1, 0, 0));
+}
+
+macro_rules! major_version {
+    ($n:literal) => {
+        const _: () = assert!($n == 0);
+    };
+}
+
+seq!(N in 0..=env!("CARGO_PKG_VERSION_MAJOR") {
+    major_version!(N);
+});
+
+#[test]
+fn test_const_expr_bounds() {
+    let values = seq!(N in 0..(1 << 4) { [#(N,)*] });
+    assert_eq!(values.len(), 16);
+
+    let values = seq!(N in 0..2 * 8 + 1 { [#(N,)*] });
+    assert_eq!(values.len(), 17);
+
+    let values = seq!(N in (1 << 2)..-(-6) { [#(N,)*] });
+    assert_eq!(values, [4, 5]);
+
+    macro_rules! count {
+        ($n:expr) => {
+            seq!(N in 0..$n { [#(N,)*] })
+        };
+    }
+    assert_eq!(count!(2 + 1), [0, 1, 2]);
+
+    seq!(P in 0x00..0x01 + 1 {
+        struct Pin~P;
+    });
+    let _ = (Pin00, Pin01);
+
+    let values = seq!(N in 0u16..1 << 2 { [#(N,)*] });
+    assert_eq!(values, [0u16, 1, 2, 3]);
+}
+
+#[test]
+fn test_type_consts() {
+    fn halve(b: u8) -> u8 {
+        seq!(N in u8::MIN..=u8::MAX {
+            match b {
+                #(N => #{N / 2},)*
+            }
+        })
+    }
+    assert_eq!(halve(255), 127);
+
+    let bits = seq!(N in (0..u32::BITS).step_by(8) { [#(1u32 << N,)*] });
+    assert_eq!(bits, [1, 1 << 8, 1 << 16, 1 << 24]);
+
+    let values = seq!(N in i8::MIN..i8::MIN + 2 { [#(N,)*] });
+    assert_eq!(values, [-128i8, -127i8]);
+
+    let values = seq!(N in u64::MAX - 1..=u64::MAX { [#(N,)*] });
+    assert_eq!(values, [u64::MAX - 1, u64::MAX]);
+
+    seq!(N in 0xFD..=u8::MAX {
+        struct Byte~N;
+    });
+    let _ = (ByteFD, ByteFE, ByteFF);
+}
+
+#[test]
+fn test_open_ended() {
+    let values = seq!(N in 250u8.. { [#(N,)*] });
+    assert_eq!(values, [250, 251, 252, 253, 254, 255]);
+
+    let values = seq!(N in 0x7Ei8.. { [#(N,)*] });
+    assert_eq!(values, [0x7E, 0x7F]);
+
+    let bytes = seq!(B in b'z'.. { [#(B,)*] });
+    assert_eq!(bytes.len(), 0x100 - 0x7A);
+    assert_eq!(bytes[bytes.len() - 1], 0xFF);
+
+    let values = seq!(N in (65534u16..).rev() { [#(N,)*] });
+    assert_eq!(values, [65535, 65534]);
+}
+
+#[test]
+fn test_suffix_bounds() {
+    let values = seq!(N in 253u8..256 { [#(N,)*] });
+    assert_eq!(values, [253, 254, 255]);
+
+    let values = seq!(N in -128i8..-126 { [#(N,)*] });
+    assert_eq!(values, [-128, -127]);
+}
+
+#[test]
+fn test_allow_empty() {
+    let values: [u8; 0] = seq!(N in 0..0 where allow_empty { [#(N,)*] });
+    assert_eq!(values, []);
+
+    let cells = seq!(R in 0..3, C in 1..=R { [#((R, C),)*] });
+    assert_eq!(cells, [(1, 1), (2, 1), (2, 2)]);
+}
+
+#[test]
+fn test_empty_contexts() {
+    struct Empty;
+    impl Empty {
+        seq!(N in 0..0 {
+            fn f~N() {}
+        });
+    }
+    let _ = Empty;
+
+    let values: Vec<u8> = seq!(N in 0..0 { vec![#(N,)*] });
+    assert_eq!(values, []);
+    let max: u8 = seq!(N in 0..0 { u8::MAX #(- N)* });
+    assert_eq!(max, u8::MAX);
+}
+
+#[test]
+fn test_escapes() {
+    let bytes = seq!(B in b'\0'..=b'\x03' { [#(B,)*] });
+    assert_eq!(bytes, [0, 1, 2, 3]);
+
+    let bytes = seq!(B in b'\xfd'.. { [#(B,)*] });
+    assert_eq!(bytes, [0xFD, 0xFE, 0xFF]);
+
+    let chars = seq!(C in '\t'..='\n' { [#(C,)*] });
+    assert_eq!(chars, ['\t', '\n']);
+
+    seq!(C in '\u{3B1}'..='\u{3B3}' {
+        fn greek_~C() -> char {
+            C
+        }
+    });
+    let chars = seq!(C in '\u{3B1}'..='\u{3B3}' { [#(greek_~C(),)*] });
+    assert_eq!(chars, ['α', 'β', 'γ']);
+}
+
+#[test]
+fn test_conversions() {
+    let chars = seq!(N in 97..=99 { [#(#{N:char},)*] });
+    assert_eq!(chars, ['a', 'b', 'c']);
+
+    let bytes = seq!(N in 0x41..0x44 { [#(#{N:byte},)*] });
+    assert_eq!(bytes, [b'A', b'B', b'C']);
+
+    let codes = seq!(C in 'x'..='z' { [#(#{C:int},)*] });
+    assert_eq!(codes, [120, 121, 122]);
+
+    let names = seq!(N in -1..=1 { [#(#{N:str},)*] });
+    assert_eq!(names, ["-1", "0", "1"]);
+
+    let names = seq!(C in 'a'..='c' { [#(#{C:str},)*] });
+    assert_eq!(names, ["a", "b", "c"]);
+
+    let names = seq!(T in [u8, Vec<u8>] { [#(#{T:str},)*] });
+    assert_eq!(names, ["u8", "Vec < u8 >"]);
+
+    let flags = seq!(N in 0..2 { [#(#{N:bool},)*] });
+    assert_eq!(flags, [false, true]);
+
+    let floats = seq!(N in -1..=1 { [#(#{N:float},)*] });
+    assert_eq!(floats, [-1.0, 0.0, 1.0]);
+
+    let fields = seq!(R in [{ code: 0x2A }] { [#(#{R.code:char},)*] });
+    assert_eq!(fields, ['*']);
+
+    seq!(R in [{ name: initial }] {
+        struct Letters {
+            R.name: char,
+        }
+    });
+    let _ = Letters { initial: 'a' }.initial;
+
+    seq!(F in [name] {
+        struct Named {
+            F: str,
+        }
+    });
+    fn name(named: &Named) -> &str {
+        &named.name
+    }
+    let _ = name;
+
+    let codes = seq!(N in 0..2 { [#(#{N * 2 + 0x61:char},)*] });
+    assert_eq!(codes, ['a', 'c']);
+
+    let max = seq!(N in 0..1 { #{u8::MAX:str} });
+    assert_eq!(max, "255");
+}
+
+#[test]
+fn test_format_spec() {
+    seq!(N in 10..=11 {
+        struct Pin~N:02x;
+        struct Reg~{N:X};
+        struct Mask~N:08b;
+        fn read~{N:04}() -> u32 { N }
+    });
+    let _ = (Pin0a, Pin0b, RegA, RegB, Mask00001010, Mask00001011);
+    assert_eq!(read0010() + read0011(), 21);
+
+    seq!(P in 0x00..0x02 {
+        struct Port~{P:d};
+    });
+    let _ = (Port0, Port1);
+
+    let masks = seq!(N in 1..=2 { [#(#{N:04b},)*] });
+    assert_eq!(masks, [0b0001, 0b0010]);
+
+    seq!(C in 'a'..='b' {
+        struct Code~{C:x};
+    });
+    let _ = (Code61, Code62);
+
+    seq!(N in 0..1 {
+        struct Point~N {
+            x~N: u8,
+        }
+    });
+    assert_eq!(Point0 { x0: 7 }.x0, 7);
+
+    seq!(N in 0..1 {
+        struct Wide~{N:02x};
+    });
+    let _ = Wide00;
+}
+
+#[test]
+fn test_format_spec_field_position() {
+    struct Tup(u8, u8);
+    let (x0, x1) = (3, 4);
+    let t = seq!(N in 0..2 { Tup { #(N: x~N,)* } });
+    assert_eq!((t.0, t.1), (3, 4));
+
+    seq!(N in 0..1 {
+        struct Pair<X> {
+            field~N: X,
+        }
+        let x = 5;
+        let pair = Pair { field~N: x };
+        assert_eq!(pair.field0, 5);
+    });
+
+    seq!(N in 1..2 {
+        let Tup { N: x, .. } = t;
+    });
+    assert_eq!(x, 4);
+}
+
+#[test]
+fn test_options() {
+    seq!(N in 8..=10 where pad = auto {
+        struct Slot~N;
+    });
+    let _ = (Slot08, Slot09, Slot10);
+
+    seq!(N in 0x0..0x2 where pad = none, radix = dec {
+        struct Lane~N;
+    });
+    let _ = (Lane0, Lane1);
+
+    seq!(N in 14..=16 where radix = hex, pad = 4 {
+        struct Bank~N;
+    });
+    let _ = (Bank000e, Bank000f, Bank0010);
+
+    seq!(N in 254..=255 where radix = HEX, pad = auto {
+        struct Page~N;
+    });
+    let _ = (PageFE, PageFF);
+
+    let values = seq!(N in 0..3u16 where suffix = none { [#(N,)*] });
+    let _: [u8; 3] = values;
+
+    let values = seq!(N in 0..3 where suffix = u8 { [#(N,)*] });
+    assert_eq!(values, [0u8, 1, 2]);
+
+    seq!(R in [{ id: 1 }, { id: 12 }] where pad = auto {
+        struct Unit~R.id;
+    });
+    let _ = (Unit01, Unit12);
+
+    seq!(N in 1..=2 where radix = bin, pad = auto, allow_empty {
+        struct Flag~N;
+        struct Hex~{N:x};
+    });
+    let _ = (Flag01, Flag10, Hex1, Hex2);
+}
+
+#[test]
+fn test_digit_groups() {
+    let values = seq!(N in 0x0000_0000..=0x0000_0002 { [#(stringify!(N),)*] });
+    assert_eq!(values, ["0x0000_0000", "0x0000_0001", "0x0000_0002"]);
+
+    let values = seq!(N in 999_999..1_000_001 { [#(stringify!(N),)*] });
+    assert_eq!(values, ["999_999", "1_000_000"]);
+
+    let values = seq!(N in 0..=1_00_000 { [#(stringify!(N),)*] }).len();
+    assert_eq!(values, 100_001);
+
+    let value = seq!(N in 99_99_999..1_00_00_000 { stringify!(N) });
+    assert_eq!(value, "99_99_999");
+
+    seq!(N in 0x00_00..0x00_02 {
+        struct Reg~N;
+    });
+    let _ = (Reg0000, Reg0001);
+
+    seq!(N in 0x00_00..0x00_02 where paste_groups {
+        struct Grouped~N;
+    });
+    let _ = (Grouped00_00, Grouped00_01);
+
+    let values = seq!(N in 0x00_00..0x00_02 { [#(stringify!(#{N:04b}),)*] });
+    assert_eq!(values, ["0b0000", "0b0001"]);
 }
@@ -0,0 +1,7 @@
+use seq_macro::seq;
+
+seq!(N in 0..(1 << 200) {});
+
+seq!(N in 0..8 / (4 - 4) {});
+
+fn main() {}
@@ -0,0 +1,11 @@
+use seq_macro::seq;
+
+seq!(N in (250..300).chain(0u8..1) {
+    const _: u8 = N;
+});
+
+seq!(N in (0u8..1).chain(250..=256) {
+    const _: u8 = N;
+});
+
+fn main() {}
@@ -0,0 +1,9 @@
+use seq_macro::seq;
+
+seq!(N in 0..4 {
+    #if N % 2 {
+        struct S~N;
+    }
+});
+
+fn main() {}
@@ -0,0 +1,7 @@
+use seq_macro::seq;
+
+seq!(N in 254..=256 {
+    const B~N: u8 = #{N:byte};
+});
+
+fn main() {}
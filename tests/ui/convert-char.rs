@@ -0,0 +1,7 @@
+use seq_macro::seq;
+
+seq!(N in 0xD7FF..=0xD800 {
+    const C~N: char = #{N:char};
+});
+
+fn main() {}
@@ -0,0 +1,17 @@
+#![deny(deprecated)]
+
+use seq_macro::seq;
+
+seq!(N in 5..5 {
+    struct S~N;
+});
+
+seq!(N in 0..0 {
+    fn empty() -> [u8; 0] {
+        [#(N,)*]
+    }
+});
+
+fn main() {
+    let _: [u8; 0] = seq!(N in 0..0 where allow_empty { [#(N,)*] });
+}
@@ -0,0 +1,5 @@
+use seq_macro::seq;
+
+seq!(N in 0..env!("SEQ_MACRO_UNDEFINED") {});
+
+fn main() {}
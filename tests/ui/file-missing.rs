@@ -0,0 +1,7 @@
+use seq_macro::seq;
+
+seq!(R in file("tests/ui/missing.csv") {
+    const R.name: u8 = 0;
+});
+
+fn main() {}
@@ -0,0 +1,5 @@
+use seq_macro::seq;
+
+seq!(N in -1..1u8 {});
+
+fn main() {}
@@ -0,0 +1,5 @@
+use seq_macro::seq;
+
+seq!(N in 0.. {});
+
+fn main() {}
@@ -0,0 +1,7 @@
+use seq_macro::seq;
+
+seq!(N in 250..=300 where suffix = u8 {
+    const _: u8 = N;
+});
+
+fn main() {}
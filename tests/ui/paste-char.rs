@@ -0,0 +1,7 @@
+use seq_macro::seq;
+
+seq!(C in '\x2B'..='\x2D' {
+    struct Char~C;
+});
+
+fn main() {}
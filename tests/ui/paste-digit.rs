@@ -0,0 +1,7 @@
+use seq_macro::seq;
+
+seq!(N in 0..4 {
+    struct N~_Reg;
+});
+
+fn main() {}
@@ -0,0 +1,7 @@
+use seq_macro::seq;
+
+seq!(T in [u8, Vec<u8>] {
+    struct Wrap~T(T);
+});
+
+fn main() {}
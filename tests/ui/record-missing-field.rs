@@ -0,0 +1,7 @@
+use seq_macro::seq;
+
+seq!(R in [{ name: ctrl, addr: 0x00 }, { name: status }] {
+    const R.name: usize = R.addr;
+});
+
+fn main() {}
@@ -0,0 +1,7 @@
+use seq_macro::seq;
+
+seq!(N in (0u32..).step_by(2).rev().take(2) {
+    struct S~N;
+});
+
+fn main() {}
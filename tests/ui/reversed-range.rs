@@ -0,0 +1,5 @@
+use seq_macro::seq;
+
+seq!(N in 10..3 {});
+
+fn main() {}
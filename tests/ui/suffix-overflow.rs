@@ -0,0 +1,9 @@
+use seq_macro::seq;
+
+seq!(N in 250u8..=300 {});
+
+seq!(N in 0..3u8 {
+    const _: u8 = #{N * 200};
+});
+
+fn main() {}
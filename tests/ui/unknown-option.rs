@@ -0,0 +1,11 @@
+use seq_macro::seq;
+
+seq!(N in 0..4 where padding = auto {
+    struct S~N;
+});
+
+seq!(N in 0..4 where radix = hexadecimal {
+    struct T~N;
+});
+
+fn main() {}
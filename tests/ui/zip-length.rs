@@ -0,0 +1,7 @@
+use seq_macro::seq;
+
+seq!((N, T) in zip(0..4, [u8, u16, u32]) {
+    struct S~N(T);
+});
+
+fn main() {}